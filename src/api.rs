@@ -0,0 +1,435 @@
+//! Library entry point for tools that embed hackerman, such as `xtask`
+//!
+//! All the operations return owned results and leave printing to the caller:
+//!
+//! ```no_run
+//! # fn main() -> Result<(), cargo_hackerman::api::Error> {
+//! let metadata = cargo_metadata::MetadataCommand::new().exec().unwrap();
+//! let hackerman = cargo_hackerman::api::Hackerman::host(metadata)?.no_dev(true);
+//! let changeset = hackerman.changeset()?;
+//! for member in &changeset.members {
+//!     println!("{} needs {} changes", member.name, member.changes.len());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    diff::{diff, GraphDiff},
+    dupes::{
+        check_policy, duplicates, feature_duplicates, focus_on_duplicates, Allowlist, Duplicates,
+        FeatureDuplicates, PolicyCheck,
+    },
+    explain::{explain, paths, tree, DepPath, DevFilter, ExplainResult, TreeFilter},
+    feat_graph::FeatGraph,
+    hack::{changeset, force_config, get_changeset, Changeset},
+    origins::{origins, Origin},
+    report::{crate_features, member_trees, Report},
+    simulate::{variants, Variants},
+    toml::set_dependencies,
+    verify::{verify_model, MemberMismatches},
+};
+use cargo_metadata::{Metadata, Version};
+use cargo_platform::Cfg;
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    /// cargo metadata was produced without resolved dependencies
+    NoResolve,
+    /// there's no crate matching the request in the dependency graph
+    UnknownPackage {
+        name: String,
+        feature: Option<String>,
+        version: Option<Version>,
+    },
+    /// failed to query target configuration from `rustc`
+    Rustc(String),
+    /// metadata describes a dependency graph hackerman can't process or
+    /// `[workspace.metadata.hackerman]` table is invalid
+    Metadata(String),
+    /// manifest file can't be read or written
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// manifest file can't be parsed or updated
+    Manifest { path: PathBuf, message: String },
+    /// running cargo failed or produced unexpected output
+    Cargo(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NoResolve => f.write_str("Cargo couldn't produce resolved dependencies"),
+            Error::UnknownPackage {
+                name,
+                feature,
+                version,
+            } => {
+                write!(f, "{name}")?;
+                if let Some(feature) = feature {
+                    write!(f, " with feature {feature}")?;
+                }
+                if let Some(version) = version {
+                    write!(f, " {version}")?;
+                }
+                f.write_str(" is not used")
+            }
+            Error::Rustc(msg) => write!(f, "Couldn't get target configuration: {msg}"),
+            Error::Metadata(msg) => write!(f, "Invalid metadata: {msg}"),
+            Error::Io { path, source } => write!(f, "Couldn't access {}: {source}", path.display()),
+            Error::Manifest { path, message } => {
+                write!(f, "Couldn't update {}: {message}", path.display())
+            }
+            Error::Cargo(msg) => write!(f, "Cargo failed: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl Error {
+    fn metadata(err: anyhow::Error) -> Self {
+        Error::Metadata(format!("{err:#}"))
+    }
+
+    fn manifest(path: &Path, err: anyhow::Error) -> Self {
+        match err.downcast::<std::io::Error>() {
+            Ok(source) => Error::Io {
+                path: path.to_path_buf(),
+                source,
+            },
+            Err(err) => Error::Manifest {
+                path: path.to_path_buf(),
+                message: format!("{err:#}"),
+            },
+        }
+    }
+}
+
+/// Configuration for hackerman operations over a single workspace
+#[derive(Debug, Clone)]
+pub struct Hackerman {
+    metadata: Metadata,
+    triplets: Vec<String>,
+    cfgs: Vec<Cfg>,
+    lock: bool,
+    no_dev: bool,
+    no_transitive_opt: bool,
+    package_nodes: bool,
+    dev_filter: DevFilter,
+    dupes_only: bool,
+    proc_macros_only: bool,
+    edge_labels: bool,
+}
+
+impl Hackerman {
+    /// Use metadata with given target triplet and its `rustc --print=cfg` configuration
+    #[must_use]
+    pub fn new(metadata: Metadata, triplet: impl Into<String>, cfgs: Vec<Cfg>) -> Self {
+        Self {
+            metadata,
+            triplets: vec![triplet.into()],
+            cfgs,
+            lock: false,
+            no_dev: false,
+            no_transitive_opt: false,
+            package_nodes: false,
+            dev_filter: DevFilter::All,
+            dupes_only: false,
+            proc_macros_only: false,
+            edge_labels: false,
+        }
+    }
+
+    /// Use metadata with the current target, configuration is taken from `rustc`
+    pub fn host(metadata: Metadata) -> Result<Self, Error> {
+        let platform =
+            target_spec::Platform::current().map_err(|err| Error::Rustc(err.to_string()))?;
+        Ok(Self::new(metadata, platform.triple_str(), host_cfgs()?))
+    }
+
+    /// Include dependencies checksum into stash
+    #[must_use]
+    pub fn lock(mut self, lock: bool) -> Self {
+        self.lock = lock;
+        self
+    }
+
+    /// Don't unify dev dependencies
+    #[must_use]
+    pub fn no_dev(mut self, no_dev: bool) -> Self {
+        self.no_dev = no_dev;
+        self
+    }
+
+    /// Don't strip redundant links in `explain` and `tree`
+    #[must_use]
+    pub fn no_transitive_opt(mut self, no_transitive_opt: bool) -> Self {
+        self.no_transitive_opt = no_transitive_opt;
+        self
+    }
+
+    /// Use package nodes instead of feature nodes in `explain` and `tree`
+    #[must_use]
+    pub fn package_nodes(mut self, package_nodes: bool) -> Self {
+        self.package_nodes = package_nodes;
+        self
+    }
+
+    /// Dependency kinds `explain` and `paths` follow
+    #[must_use]
+    pub fn dev_filter(mut self, dev_filter: DevFilter) -> Self {
+        self.dev_filter = dev_filter;
+        self
+    }
+
+    /// Keep only parts of `explain` and `tree` graphs leading to crates present in more
+    /// than one version
+    #[must_use]
+    pub fn dupes_only(mut self, dupes_only: bool) -> Self {
+        self.dupes_only = dupes_only;
+        self
+    }
+
+    /// Keep only parts of `explain` and `tree` graphs going through procedural macros, useful
+    /// for compile time investigations
+    #[must_use]
+    pub fn proc_macros_only(mut self, proc_macros_only: bool) -> Self {
+        self.proc_macros_only = proc_macros_only;
+        self
+    }
+
+    /// Label `explain` and `tree` links with dependency kinds, platforms and renames
+    #[must_use]
+    pub fn edge_labels(mut self, edge_labels: bool) -> Self {
+        self.edge_labels = edge_labels;
+        self
+    }
+
+    #[must_use]
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Feature graph for lower level operations
+    pub fn feat_graph(&self) -> Result<FeatGraph<'_>, Error> {
+        if self.metadata.resolve.is_none() {
+            return Err(Error::NoResolve);
+        }
+        let triplets = self.triplets.iter().map(String::as_str).collect();
+        FeatGraph::init(&self.metadata, triplets, self.cfgs.clone()).map_err(Error::metadata)
+    }
+
+    /// Options can be overridden by `[workspace.metadata.hackerman]` table
+    fn workspace_config(&self) -> (bool, bool) {
+        let (mut lock, mut no_dev) = (self.lock, self.no_dev);
+        force_config(&mut lock, "lock", &self.metadata.workspace_metadata);
+        force_config(&mut no_dev, "no-dev", &self.metadata.workspace_metadata);
+        (lock, no_dev)
+    }
+
+    /// Calculate changes required to unify features without applying them
+    pub fn changeset(&self) -> Result<Changeset, Error> {
+        let (_lock, no_dev) = self.workspace_config();
+        let mut fg = self.feat_graph()?;
+        changeset(&mut fg, no_dev).map_err(Error::metadata)
+    }
+
+    /// Calculate changes required to unify features and write them to manifest files
+    ///
+    /// Cargo.lock file needs to be regenerated afterwards
+    pub fn hack(&self) -> Result<Changeset, Error> {
+        let (lock, _no_dev) = self.workspace_config();
+        let changeset = self.changeset()?;
+        for member in &changeset.members {
+            set_dependencies(&member.manifest_path, lock, &member.changes)
+                .map_err(|err| Error::manifest(member.manifest_path.as_std_path(), err))?;
+        }
+        Ok(changeset)
+    }
+
+    /// Explain why a crate is present in the dependency graph
+    pub fn explain(
+        &self,
+        krate: &str,
+        feature: Option<&str>,
+        version: Option<&Version>,
+    ) -> Result<ExplainResult, Error> {
+        let mut fg = self.feat_graph()?;
+        fg.optimize(self.no_transitive_opt)
+            .map_err(Error::metadata)?;
+        explain(
+            &mut fg,
+            krate,
+            feature,
+            version,
+            self.package_nodes,
+            self.dev_filter,
+        )
+        .map_err(Error::metadata)?;
+        if fg.focus_nodes.as_ref().is_none_or(|nodes| nodes.is_empty()) {
+            return Err(unknown_package(krate, feature, version));
+        }
+        if self.dupes_only {
+            focus_on_duplicates(&mut fg);
+        }
+        if self.proc_macros_only {
+            fg.focus_through(FeatGraph::is_proc_macro, true);
+        }
+        fg.edge_labels = self.edge_labels;
+        ExplainResult::from_focus(&fg).map_err(Error::metadata)
+    }
+
+    /// Shortest distinct dependency paths from workspace members to a crate
+    ///
+    /// Each path is a list of steps such as `["alpha", "reqwest:default-tls", "native-tls"]`
+    /// along with kinds of dependencies involved, `limit` applies to each member separately
+    /// and paths are grouped by member
+    pub fn paths(
+        &self,
+        krate: &str,
+        feature: Option<&str>,
+        version: Option<&Version>,
+        limit: usize,
+    ) -> Result<Vec<DepPath>, Error> {
+        let mut fg = self.feat_graph()?;
+        paths(&mut fg, krate, feature, version, limit, self.dev_filter)
+            .map_err(Error::metadata)?
+            .ok_or_else(|| unknown_package(krate, feature, version))
+    }
+
+    /// Manifest declarations responsible for enabling a crate or one of its features
+    pub fn origins(
+        &self,
+        krate: &str,
+        feature: Option<&str>,
+        version: Option<&Version>,
+    ) -> Result<Vec<Origin>, Error> {
+        let mut fg = self.feat_graph()?;
+        origins(&mut fg, krate, feature, version)
+            .map_err(Error::metadata)?
+            .ok_or_else(|| unknown_package(krate, feature, version))
+    }
+
+    /// Dependencies of a crate or the whole workspace if crate is not specified
+    pub fn tree(
+        &self,
+        krate: Option<&str>,
+        feature: Option<&str>,
+        version: Option<&Version>,
+        filter: &TreeFilter,
+    ) -> Result<ExplainResult, Error> {
+        let mut fg = self.feat_graph()?;
+        fg.optimize(self.no_transitive_opt)
+            .map_err(Error::metadata)?;
+        tree(&mut fg, krate, feature, version, self.package_nodes, filter)
+            .map_err(Error::metadata)?;
+        if let Some(krate) = krate {
+            if fg.focus_nodes.as_ref().is_none_or(|nodes| nodes.is_empty()) {
+                return Err(unknown_package(krate, feature, version));
+            }
+        }
+        if self.dupes_only {
+            focus_on_duplicates(&mut fg);
+        }
+        if self.proc_macros_only {
+            fg.focus_through(FeatGraph::is_proc_macro, true);
+        }
+        fg.edge_labels = self.edge_labels;
+        ExplainResult::from_focus(&fg).map_err(Error::metadata)
+    }
+
+    /// Crates present in more than one version
+    pub fn duplicates(&self) -> Result<Duplicates, Error> {
+        let mut fg = self.feat_graph()?;
+        duplicates(&mut fg).map_err(Error::metadata)
+    }
+
+    /// Crates, versions and features that differ from an older state of the workspace
+    pub fn diff(&self, old: &Hackerman) -> Result<GraphDiff, Error> {
+        let mut old = old.feat_graph()?;
+        let mut new = self.feat_graph()?;
+        diff(&mut old, &mut new).map_err(Error::metadata)
+    }
+
+    /// Crates compiled with different sets of features when members are built individually
+    pub fn feature_duplicates(&self) -> Result<FeatureDuplicates, Error> {
+        let (_lock, no_dev) = self.workspace_config();
+        let fg = self.feat_graph()?;
+        Ok(feature_duplicates(&fg, no_dev))
+    }
+
+    /// Crates compiled with different sets of features when building the whole workspace
+    /// and each member individually, either as is or after applying [`Hackerman::changeset`]
+    pub fn variants(&self, hacked: bool) -> Result<Variants<'_>, Error> {
+        let (_lock, no_dev) = self.workspace_config();
+        let mut fg = self.feat_graph()?;
+        if hacked {
+            get_changeset(&mut fg, no_dev).map_err(Error::metadata)?;
+        }
+        variants(&mut fg, !no_dev).map_err(Error::metadata)
+    }
+
+    /// Check duplicates against `[workspace.metadata.hackerman.dupes]` allowlist
+    pub fn check_duplicates(&self, duplicates: &Duplicates) -> Result<PolicyCheck, Error> {
+        let allowlist = Allowlist::from_workspace_metadata(&self.metadata.workspace_metadata)
+            .map_err(Error::metadata)?;
+        Ok(check_policy(duplicates, &allowlist))
+    }
+
+    /// Compare features predicted for each member with ones `cargo tree` reports,
+    /// `manifest_path` points to the workspace `Cargo.toml`
+    pub fn verify_model(&self, manifest_path: &Path) -> Result<Vec<MemberMismatches>, Error> {
+        let mut fg = self.feat_graph()?;
+        verify_model(&mut fg, &self.metadata, manifest_path)
+            .map_err(|err| Error::Cargo(format!("{err:#}")))
+    }
+
+    /// Dependency trees, duplicates, pending `hack` changes and crate features in one go,
+    /// see [`crate::report::html`] to render them
+    pub fn report(&self) -> Result<Report, Error> {
+        let mut fg = self.feat_graph()?;
+        let duplicates = duplicates(&mut fg).map_err(Error::metadata)?;
+        Ok(Report {
+            trees: member_trees(&fg),
+            duplicates,
+            changeset: self.changeset()?,
+            features: crate_features(&fg),
+        })
+    }
+}
+
+fn unknown_package(krate: &str, feature: Option<&str>, version: Option<&Version>) -> Error {
+    Error::UnknownPackage {
+        name: krate.to_string(),
+        feature: feature.map(String::from),
+        version: version.cloned(),
+    }
+}
+
+/// Target configuration of the host as reported by `rustc --print=cfg`
+pub fn host_cfgs() -> Result<Vec<Cfg>, Error> {
+    let output = std::process::Command::new("rustc")
+        .arg("--print=cfg")
+        .output()
+        .map_err(|err| Error::Rustc(format!("rustc failed to run: {err}")))?;
+    let stdout = String::from_utf8(output.stdout).map_err(|err| Error::Rustc(err.to_string()))?;
+    stdout
+        .lines()
+        .map(Cfg::from_str)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| Error::Rustc(err.to_string()))
+}
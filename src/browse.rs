@@ -0,0 +1,707 @@
+//! Terminal browser for the feature graph
+//!
+//! Graphs with thousands of features are hard to look at as a picture, browser shows them as
+//! a tree instead: start from workspace members or from search results and expand
+//! dependencies or dependents of any node. Children are only computed when a node is expanded
+//! so this stays responsive on big workspaces.
+//!
+//! Browser state is kept separately from the terminal: it reacts to [`Key`]s and draws itself
+//! into a list of lines, the terminal part only switches the terminal into raw mode and
+//! shuffles lines and key presses around.
+
+use crate::{
+    dupes::mark_duplicates,
+    explain::name_pattern,
+    feat_graph::{Feat, FeatGraph},
+    hack::Collect,
+    origins::manifest_path,
+};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use petgraph::{graph::NodeIndex, visit::EdgeRef, EdgeDirection};
+use std::collections::BTreeSet;
+
+const HELP: &str = "\
+Up, Down, j, k          move the cursor
+PgUp, PgDn, Home, End   move the cursor faster
+Right, l, Enter         expand dependencies of the selected node
+r                       expand dependents of the selected node
+Left, h                 collapse the selected node or go to its parent
+/                       search crates by name, glob such as serde* or /regex/
+w                       go back to workspace members
+p                       toggle between package and feature nodes
+s                       show information about the selected crate
+m                       show manifest of the selected crate
+R                       show readme of the selected crate
+?                       show this help
+q, Esc                  exit";
+
+const HINT: &str =
+    "→ deps  r dependents  ← collapse  / search  p packages  s show  m manifest  ? help  q quit";
+
+/// Keys browser reacts to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Esc,
+    Backspace,
+    Interrupt,
+    Char(char),
+}
+
+/// Top level nodes of the tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum View {
+    Members,
+    Search(String),
+}
+
+/// Visible line of the tree
+#[derive(Debug, Clone, Copy)]
+struct Row {
+    node: NodeIndex,
+    depth: usize,
+    /// how the node is related to its parent, top level nodes use `Outgoing`
+    dir: EdgeDirection,
+    /// which neighbours are shown under the node, if any
+    expanded: Option<EdgeDirection>,
+}
+
+/// Text shown on top of the tree until the next key press
+struct Popup {
+    lines: Vec<String>,
+    offset: usize,
+}
+
+struct Browser<'a, 'b> {
+    fg: &'b FeatGraph<'a>,
+    /// show packages instead of individual features
+    package_nodes: bool,
+    view: View,
+    rows: Vec<Row>,
+    cursor: usize,
+    /// first visible row
+    offset: usize,
+    /// search pattern being typed
+    search: Option<String>,
+    popup: Option<Popup>,
+    /// shown in the status line until the next key press
+    message: Option<String>,
+}
+
+/// Run the browser in the current terminal until `q`
+///
+/// Starts with a tree of workspace members. With `package_nodes` set each crate is a single
+/// node, same as with `-P` for `explain` and `tree`.
+pub fn browse(fg: &mut FeatGraph, package_nodes: bool) -> anyhow::Result<()> {
+    fg.shrink_to_target(Collect::DevTarget)?;
+    mark_duplicates(fg);
+    let mut browser = Browser::new(fg, package_nodes);
+    let mut terminal = term::Terminal::open()?;
+    loop {
+        let (width, height) = terminal.size();
+        terminal.draw(&browser.draw(width, height))?;
+        for key in terminal.read_keys()? {
+            if !browser.key(key) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl<'a, 'b> Browser<'a, 'b> {
+    fn new(fg: &'b FeatGraph<'a>, package_nodes: bool) -> Self {
+        let mut browser = Browser {
+            fg,
+            package_nodes,
+            view: View::Members,
+            rows: Vec::new(),
+            cursor: 0,
+            offset: 0,
+            search: None,
+            popup: None,
+            message: None,
+        };
+        browser.reset(View::Members);
+        browser
+    }
+
+    /// React to a key press, returns `false` once it's time to stop
+    fn key(&mut self, key: Key) -> bool {
+        self.message = None;
+        if key == Key::Interrupt {
+            return false;
+        }
+        if let Some(popup) = &mut self.popup {
+            match key {
+                Key::Up | Key::Char('k') => popup.offset = popup.offset.saturating_sub(1),
+                Key::Down | Key::Char('j') => {
+                    popup.offset = (popup.offset + 1).min(popup.lines.len().saturating_sub(1));
+                }
+                _ => self.popup = None,
+            }
+            return true;
+        }
+        if let Some(search) = &mut self.search {
+            match key {
+                Key::Enter => {
+                    let pattern = std::mem::take(search);
+                    self.search = None;
+                    self.reset(View::Search(pattern));
+                }
+                Key::Esc => self.search = None,
+                Key::Backspace => {
+                    search.pop();
+                }
+                Key::Char(c) => search.push(c),
+                _ => {}
+            }
+            return true;
+        }
+
+        let page = 10;
+        match key {
+            Key::Char('q') | Key::Esc => return false,
+            Key::Up | Key::Char('k') => self.cursor = self.cursor.saturating_sub(1),
+            Key::Down | Key::Char('j') => self.cursor += 1,
+            Key::PageUp => self.cursor = self.cursor.saturating_sub(page),
+            Key::PageDown => self.cursor += page,
+            Key::Home => self.cursor = 0,
+            Key::End => self.cursor = self.rows.len(),
+            Key::Right | Key::Enter | Key::Char('l') => self.expand(EdgeDirection::Outgoing),
+            Key::Char('r') => self.expand(EdgeDirection::Incoming),
+            Key::Left | Key::Char('h') => self.collapse_or_parent(),
+            Key::Char('/') => self.search = Some(String::new()),
+            Key::Char('w') => self.reset(View::Members),
+            Key::Char('p') => {
+                self.package_nodes = !self.package_nodes;
+                self.reset(self.view.clone());
+            }
+            Key::Char('s') => self.show(),
+            Key::Char('m') => self.manifest(),
+            Key::Char('R') => self.readme(),
+            Key::Char('?') => self.popup(HELP.lines().map(String::from).collect()),
+            _ => self.message = Some(String::from("Unknown key, press ? for help")),
+        }
+        self.cursor = self.cursor.min(self.rows.len().saturating_sub(1));
+        true
+    }
+
+    /// Screen contents, exactly `height` lines at most `width` characters each
+    fn draw(&mut self, width: usize, height: usize) -> Vec<String> {
+        let body = height.saturating_sub(2);
+        let mut lines = Vec::with_capacity(height);
+        let status;
+        if let Some(popup) = &self.popup {
+            lines.push(String::new());
+            lines.extend(popup.lines.iter().skip(popup.offset).take(body).cloned());
+            status = String::from("↑↓ scroll, any other key to go back");
+        } else {
+            let mode = if self.package_nodes {
+                "package"
+            } else {
+                "feature"
+            };
+            lines.push(match &self.view {
+                View::Members => format!("Workspace members, {mode} nodes"),
+                View::Search(pattern) => format!("Crates matching {pattern}, {mode} nodes"),
+            });
+
+            if self.cursor < self.offset {
+                self.offset = self.cursor;
+            } else if body > 0 && self.cursor >= self.offset + body {
+                self.offset = self.cursor + 1 - body;
+            }
+            if self.rows.is_empty() {
+                lines.push(String::from("  Nothing to show"));
+            }
+            for (ix, row) in self.rows.iter().enumerate().skip(self.offset).take(body) {
+                let marker = match row.expanded {
+                    Some(_) => '▾',
+                    None => '▸',
+                };
+                let arrow = match row.dir {
+                    EdgeDirection::Outgoing => "",
+                    EdgeDirection::Incoming => "← ",
+                };
+                let line = format!(
+                    "{}{marker} {arrow}{}",
+                    "  ".repeat(row.depth),
+                    self.label(row.node)
+                );
+                lines.push(if ix == self.cursor {
+                    format!("\x1b[7m{}\x1b[0m", truncate(&line, width))
+                } else {
+                    line
+                });
+            }
+            status = match (&self.search, &self.message) {
+                (Some(search), _) => format!("/{search}"),
+                (None, Some(message)) => message.clone(),
+                (None, None) => String::from(HINT),
+            };
+        }
+        let mut lines = lines
+            .into_iter()
+            .map(|line| {
+                if line.starts_with('\x1b') {
+                    line
+                } else {
+                    truncate(&line, width)
+                }
+            })
+            .collect::<Vec<_>>();
+        lines.resize(height.saturating_sub(1), String::new());
+        lines.push(truncate(&status, width));
+        lines
+    }
+
+    /// Replace the tree with top level nodes of a view
+    fn reset(&mut self, view: View) {
+        let nodes = match &view {
+            View::Members => self
+                .fg
+                .workspace_members
+                .iter()
+                .filter_map(|&pid| self.fg.fid_cache.get(&pid.base()))
+                .map(|&node| self.node(node))
+                .collect(),
+            View::Search(pattern) => match name_pattern(pattern) {
+                Ok(pattern) => self
+                    .fg
+                    .features
+                    .node_indices()
+                    .filter(|&node| {
+                        self.fg.features[node]
+                            .pid()
+                            .is_some_and(|pid| pattern.is_match(&pid.package().name))
+                    })
+                    .map(|node| self.node(node))
+                    .collect(),
+                Err(err) => {
+                    self.message = Some(err.to_string());
+                    return;
+                }
+            },
+        };
+        self.rows = self
+            .sorted(nodes)
+            .into_iter()
+            .map(|node| Row {
+                node,
+                depth: 0,
+                dir: EdgeDirection::Outgoing,
+                expanded: None,
+            })
+            .collect();
+        self.view = view;
+        self.cursor = 0;
+        self.offset = 0;
+    }
+
+    /// Show dependencies or dependents of the selected node under it
+    fn expand(&mut self, dir: EdgeDirection) {
+        let Some(row) = self.rows.get(self.cursor).copied() else {
+            return;
+        };
+        if row.expanded == Some(dir) {
+            return;
+        }
+        let children = self.sorted(self.collect(row.node, dir));
+        if children.is_empty() {
+            let what = match dir {
+                EdgeDirection::Outgoing => "dependencies",
+                EdgeDirection::Incoming => "dependents",
+            };
+            self.message = Some(format!("{} has no {what}", self.label(row.node)));
+            return;
+        }
+        self.collapse(self.cursor);
+        let children = children.into_iter().map(|node| Row {
+            node,
+            depth: row.depth + 1,
+            dir,
+            expanded: None,
+        });
+        self.rows.splice(self.cursor + 1..self.cursor + 1, children);
+        self.rows[self.cursor].expanded = Some(dir);
+    }
+
+    /// Remove everything shown under a row
+    fn collapse(&mut self, ix: usize) {
+        let depth = self.rows[ix].depth;
+        let end = self.rows[ix + 1..]
+            .iter()
+            .position(|row| row.depth <= depth)
+            .map_or(self.rows.len(), |pos| ix + 1 + pos);
+        self.rows.drain(ix + 1..end);
+        self.rows[ix].expanded = None;
+    }
+
+    fn collapse_or_parent(&mut self) {
+        let Some(row) = self.rows.get(self.cursor) else {
+            return;
+        };
+        if row.expanded.is_some() {
+            self.collapse(self.cursor);
+        } else if let Some(parent) = self.rows[..self.cursor]
+            .iter()
+            .rposition(|parent| parent.depth < row.depth)
+        {
+            self.cursor = parent;
+        }
+    }
+
+    fn popup(&mut self, lines: Vec<String>) {
+        self.popup = Some(Popup { lines, offset: 0 });
+    }
+
+    /// Node as it's shown in the current mode
+    fn node(&self, node: NodeIndex) -> NodeIndex {
+        if self.package_nodes {
+            self.fg.base_node(node).unwrap_or(node)
+        } else {
+            node
+        }
+    }
+
+    fn label(&self, node: NodeIndex) -> String {
+        let mut label = self.fg.package_label(node);
+        match self.fg.features[node].fid().map(|fid| fid.dep) {
+            Some(Feat::Named(name)) => label.push_str(&format!(" feature {name:?}")),
+            Some(Feat::Base) => {
+                if let Some(markers) = self.fg.package_markers(node) {
+                    label.push_str(&format!(" ({markers})"));
+                }
+                if self.fg.is_dupe(node) {
+                    label.push_str(" (duplicate)");
+                }
+            }
+            None => {}
+        }
+        label
+    }
+
+    fn sorted(&self, nodes: BTreeSet<NodeIndex>) -> Vec<NodeIndex> {
+        let mut nodes = nodes
+            .into_iter()
+            .map(|node| (self.label(node), node))
+            .collect::<Vec<_>>();
+        nodes.sort();
+        nodes.into_iter().map(|(_, node)| node).collect()
+    }
+
+    /// Direct neighbours of a node, in package mode links between features of the same
+    /// package are skipped and links from any feature of a package count
+    fn collect(&self, node: NodeIndex, dir: EdgeDirection) -> BTreeSet<NodeIndex> {
+        let sources = if self.package_nodes {
+            let pid = self.fg.features[node].pid();
+            self.fg
+                .features
+                .node_indices()
+                .filter(|&ix| pid.is_some() && self.fg.features[ix].pid() == pid)
+                .collect::<Vec<_>>()
+        } else {
+            vec![node]
+        };
+        let mut res = BTreeSet::new();
+        for source in sources {
+            for edge in self.fg.features.edges_directed(source, dir) {
+                let next = match dir {
+                    EdgeDirection::Outgoing => edge.target(),
+                    EdgeDirection::Incoming => edge.source(),
+                };
+                if self.fg.features[next].fid().is_none() {
+                    continue;
+                }
+                let next = self.node(next);
+                if next != node {
+                    res.insert(next);
+                }
+            }
+        }
+        res
+    }
+
+    fn selected(&self) -> Option<NodeIndex> {
+        self.rows.get(self.cursor).map(|row| row.node)
+    }
+
+    fn show(&mut self) {
+        let Some(node) = self.selected().and_then(|n| self.fg.base_node(n)) else {
+            return;
+        };
+        let fid = self.fg.features[node]
+            .fid()
+            .expect("package nodes have fids");
+        let package = fid.pid.package();
+        let mut lines = vec![format!("{} {}", package.name, package.version)];
+        if let Some(description) = &package.description {
+            lines.push(format!("    {description}"));
+        }
+        let source = package
+            .source
+            .as_ref()
+            .map_or_else(|| String::from("local"), ToString::to_string);
+        lines.push(format!("source: {source}"));
+        lines.push(format!("manifest: {}", package.manifest_path));
+        if let Some(license) = &package.license {
+            lines.push(format!("license: {license}"));
+        }
+        if let Some(markers) = self.fg.package_markers(node) {
+            lines.push(format!("markers: {markers}"));
+        }
+        let mut features = self
+            .fg
+            .features
+            .node_weights()
+            .filter_map(|f| f.fid())
+            .filter(|f| f.pid == fid.pid)
+            .filter_map(|f| match f.dep {
+                Feat::Named(name) => Some(name),
+                Feat::Base => None,
+            })
+            .collect::<Vec<_>>();
+        features.sort_unstable();
+        lines.push(format!("features in use: {}", features.join(", ")));
+        self.popup(lines);
+    }
+
+    fn manifest(&mut self) {
+        let Some(pid) = self.selected().and_then(|n| self.fg.features[n].pid()) else {
+            return;
+        };
+        let path = manifest_path(pid.package());
+        match std::fs::read_to_string(&path) {
+            Ok(manifest) => self.popup(manifest.lines().map(String::from).collect()),
+            Err(err) => self.message = Some(format!("Couldn't read {path}: {err}")),
+        }
+    }
+
+    fn readme(&mut self) {
+        let Some(pid) = self.selected().and_then(|n| self.fg.features[n].pid()) else {
+            return;
+        };
+        let package = pid.package();
+        match &package.readme {
+            Some(path) => match std::fs::read_to_string(path) {
+                Ok(readme) => self.popup(readme.lines().map(String::from).collect()),
+                Err(err) => self.message = Some(format!("Couldn't read {path}: {err}")),
+            },
+            None => {
+                self.message = Some(format!(
+                    "Package {} v{} defines no readme",
+                    package.name, package.version
+                ));
+            }
+        }
+    }
+}
+
+fn truncate(line: &str, width: usize) -> String {
+    line.chars().take(width).collect()
+}
+
+/// Turn a key press reported by the terminal into a [`Key`]
+fn convert_key(event: KeyEvent) -> Option<Key> {
+    if event.kind == KeyEventKind::Release {
+        return None;
+    }
+    Some(match event.code {
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::Left => Key::Left,
+        KeyCode::Right => Key::Right,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Esc => Key::Esc,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::CONTROL) => Key::Interrupt,
+        KeyCode::Char(c) => Key::Char(c),
+        _ => return None,
+    })
+}
+
+mod term {
+    use super::{convert_key, Key};
+    use crossterm::{
+        cursor,
+        event::{self, Event},
+        execute, queue,
+        terminal::{self, ClearType},
+    };
+    use std::io::{IsTerminal, Write};
+
+    /// Terminal in raw mode showing the alternate screen
+    ///
+    /// Terminal is restored once this is dropped, including unwinding after a panic
+    pub(super) struct Terminal;
+
+    impl Terminal {
+        pub(super) fn open() -> anyhow::Result<Self> {
+            if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+                anyhow::bail!("browse needs an interactive terminal");
+            }
+            terminal::enable_raw_mode()?;
+            let terminal = Terminal;
+            execute!(
+                std::io::stdout(),
+                terminal::EnterAlternateScreen,
+                cursor::Hide
+            )?;
+            Ok(terminal)
+        }
+
+        /// Width and height in characters
+        pub(super) fn size(&self) -> (usize, usize) {
+            match terminal::size() {
+                Ok((width, height)) if width > 0 && height > 0 => {
+                    (usize::from(width), usize::from(height))
+                }
+                _ => (80, 24),
+            }
+        }
+
+        pub(super) fn draw(&mut self, lines: &[String]) -> std::io::Result<()> {
+            let mut out = std::io::stdout().lock();
+            for (ix, line) in lines.iter().enumerate() {
+                queue!(
+                    out,
+                    cursor::MoveTo(0, u16::try_from(ix).unwrap_or(u16::MAX))
+                )?;
+                out.write_all(line.as_bytes())?;
+                queue!(out, terminal::Clear(ClearType::UntilNewLine))?;
+            }
+            out.flush()
+        }
+
+        /// Wait for the next key press, resizing the terminal produces no keys
+        pub(super) fn read_keys(&mut self) -> std::io::Result<Vec<Key>> {
+            Ok(match event::read()? {
+                Event::Key(key) => convert_key(key).into_iter().collect(),
+                _ => Vec::new(),
+            })
+        }
+    }
+
+    impl Drop for Terminal {
+        fn drop(&mut self) {
+            let _ = execute!(
+                std::io::stdout(),
+                cursor::Show,
+                terminal::LeaveAlternateScreen
+            );
+            let _ = terminal::disable_raw_mode();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::process_fg_with;
+
+    fn screen(browser: &mut Browser, keys: &[Key]) -> String {
+        for &key in keys {
+            assert!(browser.key(key));
+        }
+        let lines = browser.draw(40, 8);
+        assert_eq!(lines.len(), 8);
+        lines
+            .into_iter()
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn browse_dependencies() -> anyhow::Result<()> {
+        process_fg_with(12, |fg| {
+            fg.shrink_to_target(Collect::DevTarget)?;
+            mark_duplicates(fg);
+            let mut browser = Browser::new(fg, true);
+            let expected = "Workspace members, package nodes
+▸ alpha
+\x1b[7m▸ beta\x1b[0m
+▸ gamma
+/bet*";
+            let keys = "/bet*".chars().map(Key::Char).collect::<Vec<_>>();
+            let _ = screen(&mut browser, &[Key::Down]);
+            assert_eq!(screen(&mut browser, &keys), expected);
+            let expected = "Crates matching bet*, package nodes
+\x1b[7m▾ beta\x1b[0m
+  ▸ delta 0.2.0 (duplicate)
+  ▸ gamma
+→ deps  r dependents  ← collapse  / sear";
+            assert_eq!(screen(&mut browser, &[Key::Enter, Key::Right]), expected);
+            let expected = "Crates matching bet*, package nodes
+▾ beta
+\x1b[7m  ▾ delta 0.2.0 (duplicate)\x1b[0m
+    ▸ ← beta
+  ▸ gamma
+delta 0.2.0 (duplicate) has no dependenc";
+            let keys = [
+                Key::Down,
+                Key::Char('r'),
+                Key::Down,
+                Key::Left,
+                Key::Char('l'),
+            ];
+            assert_eq!(screen(&mut browser, &keys), expected);
+            let expected = "Crates matching bet*, package nodes
+\x1b[7m▸ beta\x1b[0m
+→ deps  r dependents  ← collapse  / sear";
+            assert_eq!(
+                screen(&mut browser, &[Key::Left, Key::Left, Key::Left]),
+                expected
+            );
+            let expected = "Workspace members, feature nodes
+\x1b[7m▸ alpha\x1b[0m
+▸ beta
+▸ gamma
+→ deps  r dependents  ← collapse  / sear";
+            assert_eq!(
+                screen(&mut browser, &[Key::Char('w'), Key::Char('p')]),
+                expected
+            );
+            assert!(!browser.key(Key::Char('q')));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn terminal_keys() {
+        let press = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let keys = [
+            press(KeyCode::Up),
+            press(KeyCode::PageUp),
+            press(KeyCode::Char('j')),
+            press(KeyCode::Enter),
+            press(KeyCode::F(1)),
+            KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL),
+        ]
+        .into_iter()
+        .filter_map(convert_key)
+        .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [
+                Key::Up,
+                Key::PageUp,
+                Key::Char('j'),
+                Key::Enter,
+                Key::Interrupt
+            ]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Compare dependencies of two versions of the workspace
+//!
+//! Either side can be a git revision or a file with saved `cargo metadata` output. Revisions
+//! are checked out into a temporary git worktree so the working copy stays untouched.
+
+use crate::{
+    feat_graph::{Feat, FeatGraph},
+    hack::Collect,
+    opts::{read_metadata, Profile},
+};
+use cargo_metadata::{Metadata, Version};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tracing::debug;
+
+/// Differences between two dependency graphs, everything is sorted by crate name
+#[derive(Debug, Default)]
+pub struct GraphDiff {
+    /// crates not present in the old graph in any version
+    pub added: Vec<(String, Version)>,
+    /// crates not present in the new graph in any version
+    pub removed: Vec<(String, Version)>,
+    /// crates present in both graphs but in different versions
+    pub versions: Vec<VersionChange>,
+    /// crates compiled with a different set of features
+    pub features: Vec<FeatureChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionChange {
+    pub name: String,
+    pub old: Vec<Version>,
+    pub new: Vec<Version>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeatureChange {
+    pub name: String,
+    /// version in the new graph
+    pub version: Version,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl GraphDiff {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.versions.is_empty()
+            && self.features.is_empty()
+    }
+}
+
+/// crate name -> version -> named features
+type Crates = BTreeMap<String, BTreeMap<Version, BTreeSet<String>>>;
+
+fn crates(fg: &mut FeatGraph) -> anyhow::Result<Crates> {
+    fg.shrink_to_target(Collect::DevTarget)?;
+    let mut res = Crates::new();
+    for fid in fg.features.node_weights().filter_map(|f| f.fid()) {
+        let package = fid.pid.package();
+        let features = res
+            .entry(package.name.clone())
+            .or_default()
+            .entry(package.version.clone())
+            .or_default();
+        if let Feat::Named(name) = fid.dep {
+            features.insert(name.to_string());
+        }
+    }
+    Ok(res)
+}
+
+/// Compare crates, versions and features used by two graphs for the current target
+pub fn diff(old: &mut FeatGraph, new: &mut FeatGraph) -> anyhow::Result<GraphDiff> {
+    let old = crates(old)?;
+    let new = crates(new)?;
+    let mut res = GraphDiff::default();
+
+    for (name, versions) in &old {
+        if !new.contains_key(name) {
+            for version in versions.keys() {
+                res.removed.push((name.clone(), version.clone()));
+            }
+        }
+    }
+
+    for (name, new_versions) in &new {
+        let old_versions = match old.get(name) {
+            Some(versions) => versions,
+            None => {
+                for version in new_versions.keys() {
+                    res.added.push((name.clone(), version.clone()));
+                }
+                continue;
+            }
+        };
+        if old_versions.keys().ne(new_versions.keys()) {
+            res.versions.push(VersionChange {
+                name: name.clone(),
+                old: old_versions.keys().cloned().collect(),
+                new: new_versions.keys().cloned().collect(),
+            });
+        }
+
+        // a single version upgraded to another one is still the same crate for this purpose
+        let pairs = match (old_versions.len(), new_versions.len()) {
+            (1, 1) => old_versions.values().zip(new_versions).collect::<Vec<_>>(),
+            _ => new_versions
+                .iter()
+                .filter_map(|(version, feats)| Some((old_versions.get(version)?, (version, feats))))
+                .collect(),
+        };
+        for (old_feats, (version, new_feats)) in pairs {
+            if old_feats != new_feats {
+                res.features.push(FeatureChange {
+                    name: name.clone(),
+                    version: version.clone(),
+                    added: new_feats.difference(old_feats).cloned().collect(),
+                    removed: old_feats.difference(new_feats).cloned().collect(),
+                });
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Metadata for a saved `cargo metadata` output or for a git revision of the workspace
+pub fn metadata(profile: &Profile, spec: &str) -> anyhow::Result<Metadata> {
+    if spec == "-" || Path::new(spec).is_file() {
+        debug!("Reading metadata from {spec}");
+        return read_metadata(Path::new(spec));
+    }
+
+    let manifest = std::fs::canonicalize(&profile.manifest_path)?;
+    let dir = manifest.parent().unwrap_or_else(|| Path::new("."));
+    let root = git(dir, &["rev-parse", "--show-toplevel"])?;
+    let relative = manifest.strip_prefix(root.trim())?.to_path_buf();
+
+    let temp = tempfile::tempdir()?;
+    let worktree = Worktree {
+        repo: dir.to_path_buf(),
+        path: temp.path().join("worktree"),
+    };
+    debug!("Checking out {spec} into {}", worktree.path.display());
+    git(
+        dir,
+        &[
+            "worktree",
+            "add",
+            "--detach",
+            &worktree.path.to_string_lossy(),
+            spec,
+        ],
+    )?;
+    profile.exec_at(&worktree.path.join(relative))
+}
+
+/// Temporary git worktree, removed once dropped
+struct Worktree {
+    repo: PathBuf,
+    path: PathBuf,
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        let path = self.path.to_string_lossy();
+        if let Err(err) = git(&self.repo, &["worktree", "remove", "--force", &path]) {
+            debug!("Couldn't remove worktree {path}: {err}");
+        }
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new("git").current_dir(dir).args(args).output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::process_fg_with;
+
+    #[test]
+    fn added_and_removed_crates() -> anyhow::Result<()> {
+        process_fg_with(11, |old| {
+            process_fg_with(12, |new| {
+                let res = diff(old, new)?;
+                let delta = |v: &str| (String::from("delta"), Version::parse(v).unwrap());
+                assert_eq!(res.added, vec![delta("0.1.0"), delta("0.2.0")]);
+                assert!(res.removed.is_empty());
+                assert!(res.versions.is_empty());
+                assert!(res.features.is_empty());
+
+                let res = diff(new, old)?;
+                assert_eq!(res.removed, vec![delta("0.1.0"), delta("0.2.0")]);
+                assert!(res.added.is_empty());
+                Ok(())
+            })
+        })
+    }
+}
@@ -0,0 +1,496 @@
+use crate::{
+    feat_graph::{Feat, FeatGraph, Feature, Pid},
+    hack::{member_feats, Collect},
+};
+use cargo_metadata::{Version, VersionReq};
+use petgraph::visit::EdgeRef;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Crates present in the dependency tree in more than one version
+#[derive(Debug, Default)]
+pub struct Duplicates {
+    /// crate names along with all the versions used, sorted by name
+    pub crates: Vec<Duplicate>,
+}
+
+#[derive(Debug)]
+pub struct Duplicate {
+    pub name: String,
+    pub versions: Vec<Version>,
+    /// packages depending on this crate directly, sorted by version used, then by name
+    pub dependents: Vec<Dependent>,
+    /// newest version in use satisfying every requirement, `cargo update -p` can unify them
+    pub unifiable: Option<Version>,
+}
+
+/// Package depending on one of the duplicated versions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependent {
+    /// version of the duplicated crate in use
+    pub uses: Version,
+    pub name: String,
+    pub version: Version,
+    /// requirement from the dependent's manifest
+    pub req: VersionReq,
+}
+
+impl Duplicate {
+    /// Dependents that need to be upgraded before the crate can be unified to the newest version
+    pub fn blocking(&self) -> impl Iterator<Item = &Dependent> {
+        let newest = self.versions.iter().max();
+        self.dependents
+            .iter()
+            .filter(move |dep| newest.is_some_and(|v| !dep.req.matches(v)))
+    }
+}
+
+impl Duplicates {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.crates.is_empty()
+    }
+}
+
+/// Duplicates accepted by `[workspace.metadata.hackerman.dupes]` table
+///
+/// Each entry is either a list of version requirements or a table with `versions` and an
+/// optional `reason`:
+///
+/// ```toml
+/// [workspace.metadata.hackerman.dupes]
+/// syn = ["1", "2"]
+/// windows-sys = { versions = ["0.48", "0.52"], reason = "waiting for tokio upgrade" }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Allowlist {
+    /// sorted by crate name
+    pub entries: Vec<Allowed>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Allowed {
+    pub name: String,
+    pub versions: Vec<VersionReq>,
+    pub reason: Option<String>,
+}
+
+impl Allowlist {
+    /// Read the allowlist from workspace metadata, missing table means nothing is allowed
+    pub fn from_workspace_metadata(meta: &serde_json::Value) -> anyhow::Result<Self> {
+        let table = match meta.get("hackerman").and_then(|h| h.get("dupes")) {
+            Some(table) => table,
+            None => return Ok(Self::default()),
+        };
+        let table = table.as_object().ok_or_else(|| {
+            anyhow::anyhow!("workspace.metadata.hackerman.dupes should be a table")
+        })?;
+        let mut entries = Vec::new();
+        for (name, entry) in table {
+            let invalid = || {
+                anyhow::anyhow!(
+                    "hackerman.dupes.{name} should be a list of versions or a table with versions and reason"
+                )
+            };
+            let (versions, reason) = match entry {
+                serde_json::Value::Array(versions) => (versions, None),
+                serde_json::Value::Object(entry) => (
+                    entry
+                        .get("versions")
+                        .and_then(|v| v.as_array())
+                        .ok_or_else(invalid)?,
+                    entry
+                        .get("reason")
+                        .and_then(|r| r.as_str())
+                        .map(String::from),
+                ),
+                _ => return Err(invalid()),
+            };
+            let versions = versions
+                .iter()
+                .map(|v| {
+                    let v = v.as_str().ok_or_else(invalid)?;
+                    VersionReq::parse(v)
+                        .map_err(|err| anyhow::anyhow!("hackerman.dupes.{name}: {v}: {err}"))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            entries.push(Allowed {
+                name: name.clone(),
+                versions,
+                reason,
+            });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(Self { entries })
+    }
+
+    fn get(&self, name: &str) -> Option<&Allowed> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+}
+
+/// Result of checking duplicates against the allowlist
+#[derive(Debug, Default)]
+pub struct PolicyCheck {
+    /// duplicated crates with versions not covered by the allowlist
+    pub violations: Vec<Violation>,
+    /// duplicated crates with all the versions covered by the allowlist
+    pub allowed: Vec<AllowedDuplicate>,
+    /// allowlist entries that no longer match anything
+    pub stale: Vec<StaleEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub name: String,
+    /// all the versions in use
+    pub versions: Vec<Version>,
+    /// versions not covered by the allowlist
+    pub denied: Vec<Version>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowedDuplicate {
+    pub name: String,
+    /// reason given in the allowlist
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleEntry {
+    pub name: String,
+    /// requirement matching no version in use, `None` if the crate is not duplicated at all
+    pub version: Option<String>,
+    /// reason given in the allowlist
+    pub reason: Option<String>,
+}
+
+impl PolicyCheck {
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// Check duplicates against the allowlist
+#[must_use]
+pub fn check_policy(duplicates: &Duplicates, allowlist: &Allowlist) -> PolicyCheck {
+    let mut res = PolicyCheck::default();
+    for dupe in &duplicates.crates {
+        let allowed = allowlist.get(&dupe.name);
+        let denied = dupe
+            .versions
+            .iter()
+            .filter(|v| allowed.is_none_or(|a| !a.versions.iter().any(|req| req.matches(v))))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !denied.is_empty() {
+            res.violations.push(Violation {
+                name: dupe.name.clone(),
+                versions: dupe.versions.clone(),
+                denied,
+            });
+        } else if let Some(allowed) = allowed {
+            res.allowed.push(AllowedDuplicate {
+                name: dupe.name.clone(),
+                reason: allowed.reason.clone(),
+            });
+        }
+    }
+    for entry in &allowlist.entries {
+        let dupe = match duplicates.crates.iter().find(|d| d.name == entry.name) {
+            Some(dupe) => dupe,
+            None => {
+                res.stale.push(StaleEntry {
+                    name: entry.name.clone(),
+                    version: None,
+                    reason: entry.reason.clone(),
+                });
+                continue;
+            }
+        };
+        for req in &entry.versions {
+            if !dupe.versions.iter().any(|v| req.matches(v)) {
+                res.stale.push(StaleEntry {
+                    name: entry.name.clone(),
+                    version: Some(req.to_string()),
+                    reason: entry.reason.clone(),
+                });
+            }
+        }
+    }
+    res
+}
+
+/// Crates compiled with different sets of features when members are built individually
+#[derive(Debug, Default)]
+pub struct FeatureDuplicates {
+    /// sorted by name and version
+    pub crates: Vec<FeatureDuplicate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeatureDuplicate {
+    pub name: String,
+    pub version: Version,
+    /// distinct sets of named features along with members producing them
+    pub variants: Vec<FeatureVariant>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeatureVariant {
+    /// sorted
+    pub features: Vec<String>,
+    /// sorted
+    pub members: Vec<String>,
+}
+
+impl FeatureDuplicates {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.crates.is_empty()
+    }
+}
+
+/// Collect crates with several feature sets across per-member builds
+///
+/// Uses the same per-member features as `hack` does before unifying them. Dev dependencies of
+/// a member are included unless `no_dev` is set, same as with `cargo test -p member`
+#[must_use]
+pub fn feature_duplicates(fg: &FeatGraph, no_dev: bool) -> FeatureDuplicates {
+    // package base node -> named features -> members
+    let mut variants = BTreeMap::<_, BTreeMap<BTreeSet<&str>, Vec<String>>>::new();
+    for (member, deps_feats) in member_feats(fg, no_dev) {
+        for (base, feats) in deps_feats {
+            let features = feats
+                .into_iter()
+                .filter_map(|ix| match fg.features[ix].fid()?.dep {
+                    Feat::Named(name) => Some(name),
+                    Feat::Base => None,
+                })
+                .collect();
+            variants
+                .entry(base)
+                .or_default()
+                .entry(features)
+                .or_default()
+                .push(member.package().name.clone());
+        }
+    }
+
+    let mut crates = variants
+        .into_iter()
+        .filter(|(_, variants)| variants.len() > 1)
+        .filter_map(|(base, variants)| {
+            let package = fg.features[base].pid()?.package();
+            let variants = variants
+                .into_iter()
+                .map(|(features, mut members)| {
+                    members.sort();
+                    FeatureVariant {
+                        features: features.into_iter().map(String::from).collect(),
+                        members,
+                    }
+                })
+                .collect();
+            Some(FeatureDuplicate {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                variants,
+            })
+        })
+        .collect::<Vec<_>>();
+    crates.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+    FeatureDuplicates { crates }
+}
+
+/// Collect duplicates for the current target
+pub fn duplicates(fg: &mut FeatGraph) -> anyhow::Result<Duplicates> {
+    fg.shrink_to_target(Collect::DevTarget)?;
+
+    let crates = duplicated_packages(fg)
+        .into_iter()
+        .map(|(name, pids)| {
+            let dependents = dependents(fg, &pids);
+            let mut versions = pids
+                .iter()
+                .map(|pid| pid.package().version.clone())
+                .collect::<Vec<_>>();
+            versions.sort();
+            let unifiable = versions
+                .iter()
+                .rev()
+                .find(|v| dependents.iter().all(|dep| dep.req.matches(v)))
+                .cloned();
+            Duplicate {
+                name: name.to_string(),
+                versions,
+                dependents,
+                unifiable,
+            }
+        })
+        .collect();
+    Ok(Duplicates { crates })
+}
+
+/// Direct dependents of packages along with their version requirements
+fn dependents(fg: &FeatGraph, pids: &[Pid]) -> Vec<Dependent> {
+    let mut links = BTreeSet::new();
+    for edge in fg.features.edge_references() {
+        if let (Some(parent), Some(child)) = (
+            fg.features[edge.source()].pid(),
+            fg.features[edge.target()].pid(),
+        ) {
+            if parent != child && pids.contains(&child) {
+                links.insert((parent, child));
+            }
+        }
+    }
+
+    let mut res = Vec::new();
+    for (parent, child) in links {
+        let (parent, child) = (parent.package(), child.package());
+        for dep in &parent.dependencies {
+            if dep.name == child.name && dep.req.matches(&child.version) {
+                res.push(Dependent {
+                    uses: child.version.clone(),
+                    name: parent.name.clone(),
+                    version: parent.version.clone(),
+                    req: dep.req.clone(),
+                });
+            }
+        }
+    }
+    res.sort_by(|a, b| {
+        (&a.uses, &a.name, &a.version)
+            .cmp(&(&b.uses, &b.name, &b.version))
+            .then_with(|| a.req.to_string().cmp(&b.req.to_string()))
+    });
+    res.dedup();
+    res
+}
+
+/// Packages present in more than one version grouped by name, graph should be shrunk to target
+fn duplicated_packages<'a>(fg: &FeatGraph<'a>) -> BTreeMap<&'a str, Vec<Pid<'a>>> {
+    let mut packages = BTreeMap::new();
+    for fid in fg.features.node_weights().filter_map(Feature::fid) {
+        if fid == fid.get_base() {
+            packages
+                .entry(fid.pid.package().name.as_str())
+                .or_insert_with(Vec::new)
+                .push(fid.pid);
+        }
+    }
+    packages.retain(|_, pids| pids.len() > 1);
+    packages
+}
+
+/// Remember duplicated packages so graphs can highlight them, graph should be shrunk to target
+pub fn mark_duplicates(fg: &mut FeatGraph) {
+    fg.dupes = duplicated_packages(fg).into_values().flatten().collect();
+}
+
+/// Keep only parts of the focused graph leading to duplicated packages
+pub fn focus_on_duplicates(fg: &mut FeatGraph) {
+    fg.focus_through(FeatGraph::is_dupe, false);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::explain::{tree, TreeFilter};
+    use crate::test_util::process_fg_with;
+
+    #[test]
+    fn tree_of_duplicates() -> anyhow::Result<()> {
+        process_fg_with(12, |fg| {
+            let dupes = duplicates(fg)?;
+            assert_eq!(dupes.crates.len(), 1);
+            assert_eq!(dupes.crates[0].name, "delta");
+            assert_eq!(dupes.crates[0].versions.len(), 2);
+            assert_eq!(dupes.crates[0].unifiable, None);
+            let blocking = dupes.crates[0]
+                .blocking()
+                .map(|dep| (dep.name.as_str(), dep.req.to_string()))
+                .collect::<Vec<_>>();
+            assert_eq!(blocking, [("alpha", "^0.1".to_string())]);
+
+            tree(fg, None, None, None, false, &TreeFilter::default())?;
+            focus_on_duplicates(fg);
+            let labels = fg
+                .focus_nodes
+                .iter()
+                .flatten()
+                .map(|&ix| fg.package_label(ix))
+                .collect::<BTreeSet<_>>();
+            let expected = ["alpha", "beta", "delta 0.1.0", "delta 0.2.0"];
+            assert_eq!(labels, expected.iter().map(|l| l.to_string()).collect());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn feature_sets_across_members() -> anyhow::Result<()> {
+        process_fg_with(5, |fg| {
+            let dupes = feature_duplicates(fg, false);
+            assert_eq!(dupes.crates.len(), 1);
+            assert_eq!(dupes.crates[0].name, "gamma");
+            let variant = |features: &[&str], members: &[&str]| FeatureVariant {
+                features: features.iter().map(|f| f.to_string()).collect(),
+                members: members.iter().map(|m| m.to_string()).collect(),
+            };
+            let expected = [
+                variant(&[], &["beta", "gamma"]),
+                variant(&["one"], &["alpha"]),
+            ];
+            assert_eq!(dupes.crates[0].variants, expected);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn allowlist_policy() -> anyhow::Result<()> {
+        process_fg_with(12, |fg| {
+            let dupes = duplicates(fg)?;
+
+            let check = check_policy(&dupes, &Allowlist::default());
+            assert_eq!(check.violations.len(), 1);
+            assert_eq!(check.violations[0].denied.len(), 2);
+
+            let config = serde_json::json!({"hackerman": {"dupes": {
+                "delta": {"versions": ["0.1", "0.3"], "reason": "testing"},
+                "epsilon": ["1"],
+            }}});
+            let allowlist = Allowlist::from_workspace_metadata(&config)?;
+            let check = check_policy(&dupes, &allowlist);
+            let denied = &check.violations[0].denied;
+            assert_eq!(denied, &[Version::new(0, 2, 0)]);
+            let stale = check
+                .stale
+                .iter()
+                .map(|s| (s.name.as_str(), s.version.as_deref(), s.reason.as_deref()))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                stale,
+                [
+                    ("delta", Some("^0.3"), Some("testing")),
+                    ("epsilon", None, None)
+                ]
+            );
+
+            let config = serde_json::json!({"hackerman": {"dupes": {
+                "delta": {"versions": ["0.1", "0.2"], "reason": "testing"},
+            }}});
+            let check = check_policy(&dupes, &Allowlist::from_workspace_metadata(&config)?);
+            assert!(check.is_ok());
+            let allowed = AllowedDuplicate {
+                name: String::from("delta"),
+                reason: Some(String::from("testing")),
+            };
+            assert_eq!(check.allowed, [allowed]);
+
+            let config = serde_json::json!({"hackerman": {"dupes": {"delta": "0.1"}}});
+            assert!(Allowlist::from_workspace_metadata(&config).is_err());
+            Ok(())
+        })
+    }
+}
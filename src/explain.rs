@@ -1,23 +1,75 @@
 use crate::{
-    feat_graph::{FeatGraph, HasIndex},
-    metadata::{DepKindInfo, Link},
+    dupes::mark_duplicates,
+    feat_graph::{Feat, FeatGraph, Pid},
+    hack::Collect,
+    metadata::{DepKindInfo, Link, Reach},
 };
-use cargo_metadata::Version;
+use anyhow::Context;
+use cargo_metadata::{PackageId, Version};
+use dot::GraphWalk;
 use petgraph::{
-    graph::NodeIndex,
-    visit::{Dfs, EdgeFiltered, EdgeRef, IntoEdgesDirected, Reversed},
+    graph::{EdgeReference, NodeIndex},
+    visit::{EdgeFiltered, EdgeRef, IntoEdgesDirected},
 };
-use std::collections::BTreeSet;
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use tracing::{debug, info};
 
-fn collect_packages(
+/// Turn a crate spec into a name matcher
+///
+/// Spec is either an exact crate name, a glob with `*` and `?` such as `openssl*` or
+/// a regular expression in slashes such as `/^(ring|rustls)$/`
+pub fn name_pattern(spec: &str) -> anyhow::Result<Regex> {
+    let pattern = if let Some(re) = spec
+        .strip_prefix('/')
+        .and_then(|spec| spec.strip_suffix('/'))
+    {
+        re.to_string()
+    } else {
+        let glob = regex::escape(spec).replace("\\*", ".*").replace("\\?", ".");
+        format!("^{glob}$")
+    };
+    Regex::new(&pattern).with_context(|| format!("Invalid crate pattern {spec}"))
+}
+
+/// Split comma separated crate specs, regular expressions can contain commas themselves
+///
+/// A regular expression ends with a slash followed by a comma or by the end of the input
+fn split_specs(specs: &str) -> Vec<&str> {
+    let mut res = Vec::new();
+    let mut rest = specs;
+    loop {
+        let end = match rest.strip_prefix('/') {
+            Some(re) => re.find("/,").map(|ix| ix + 2),
+            None => rest.find(','),
+        };
+        match end {
+            Some(ix) => {
+                res.push(&rest[..ix]);
+                rest = &rest[ix + 1..];
+            }
+            None => {
+                res.push(rest);
+                return res;
+            }
+        }
+    }
+}
+
+/// Find nodes matching any of comma separated crate specs, see [`name_pattern`]
+pub(crate) fn collect_packages(
     fg: &mut FeatGraph,
 
     krate: &str,
-    feature: Option<&String>,
+    feature: Option<&str>,
     version: Option<&Version>,
-) -> Vec<NodeIndex> {
-    fg.features
+) -> anyhow::Result<Vec<NodeIndex>> {
+    let patterns = split_specs(krate)
+        .into_iter()
+        .map(name_pattern)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(fg
+        .features
         .node_indices()
         .filter(|&ix| {
             if let Some(fid) = fg.features[ix].fid() {
@@ -25,29 +77,49 @@ fn collect_packages(
                 // name must match.
                 // feature must match if given, otherwise look for base
                 // version must match if given
-                package.name == krate
+                patterns.iter().any(|p| p.is_match(&package.name))
                     && feature.map_or(fid.pid.base() == fid, |f| fid.pid.named(f) == fid)
                     && version.map_or(true, |v| package.version == *v)
             } else {
                 false
             }
         })
-        .collect::<Vec<_>>()
+        .collect::<Vec<_>>())
 }
 
-pub fn tree<'a>(
-    fg: &'a mut FeatGraph<'a>,
-    krate: Option<&String>,
-    feature: Option<&String>,
+/// Which dependencies `tree` follows
+#[derive(Debug, Clone, Default)]
+pub struct TreeFilter {
+    /// keep within the workspace
+    pub workspace: bool,
+    /// skip dev-only dependencies
+    pub no_dev: bool,
+    /// stop after this many links between packages
+    pub depth: Option<usize>,
+    /// crates to stop at, see [`name_pattern`] for the syntax
+    pub prune: Vec<String>,
+    /// stop at the first external crate
+    pub externals_only_first_level: bool,
+}
+
+/// Focus on dependencies of a given crate or the whole workspace
+pub fn tree(
+    fg: &mut FeatGraph,
+    krate: Option<&str>,
+    feature: Option<&str>,
     version: Option<&Version>,
     package_nodes: bool,
-    workspace: bool,
-    no_dev: bool,
+    filter: &TreeFilter,
 ) -> anyhow::Result<()> {
-    fg.shrink_to_target()?;
+    fg.shrink_to_target(if filter.no_dev {
+        Collect::NoDev
+    } else {
+        Collect::DevTarget
+    })?;
+    mark_duplicates(fg);
 
-    let mut packages = match krate {
-        Some(krate) => collect_packages(fg, krate, feature, version),
+    let packages = match krate {
+        Some(krate) => collect_packages(fg, krate, feature, version)?,
         None => {
             let members = fg.workspace_members.clone();
             members
@@ -59,49 +131,100 @@ pub fn tree<'a>(
 
     info!("Found {} matching package(s)", packages.len());
 
+    let prune = filter
+        .prune
+        .iter()
+        .map(|spec| name_pattern(spec))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
     let g = EdgeFiltered::from_fn(&fg.features, |e| {
-        (fg.features[e.target()].is_workspace() || !workspace)
-            && (!no_dev || !e.weight().is_dev_only())
+        (fg.features[e.target()].is_workspace() || !filter.workspace)
+            && (!filter.no_dev || !e.weight().is_dev_only())
     });
 
-    let mut dfs = Dfs::new(&g, fg.root);
-
-    let mut nodes = BTreeSet::new();
-    let mut edges = BTreeSet::new();
-    let mut new_edges = BTreeSet::new();
+    let crosses = |edge: EdgeReference<Link>| {
+        fg.features[edge.source()].pid() != fg.features[edge.target()].pid()
+    };
+    // can we go from this node to a different package?
+    let can_leave = |node: NodeIndex, depth: usize| {
+        let pruned = depth > 0
+            && fg.features[node].fid().is_some_and(|fid| {
+                let name = &fid.pid.package().name;
+                prune.iter().any(|p| p.is_match(name))
+            });
+        !pruned
+            && filter.depth.is_none_or(|max| depth < max)
+            && (fg.features[node].is_workspace() || !filter.externals_only_first_level)
+    };
 
+    // breadth first search where links between packages count towards the depth and
+    // links between features of a single package don't
     debug!("Collecting dependencies");
-    while let Some(next) = packages.pop() {
-        dfs.move_to(next);
-        while let Some(node) = dfs.next(&g) {
-            let this_node = if package_nodes {
-                fg.base_node(node).expect("base node must exist")
+    let mut depths = packages
+        .iter()
+        .map(|&ix| (ix, 0))
+        .collect::<BTreeMap<_, _>>();
+    let mut queue = depths
+        .iter()
+        .map(|(&ix, &d)| (ix, d))
+        .collect::<VecDeque<_>>();
+    while let Some((node, depth)) = queue.pop_front() {
+        if depths[&node] < depth {
+            continue;
+        }
+        for edge in g.edges_directed(node, petgraph::EdgeDirection::Outgoing) {
+            let next = if crosses(edge) {
+                if !can_leave(node, depth) {
+                    continue;
+                }
+                (edge.target(), depth + 1)
             } else {
-                node
+                (edge.target(), depth)
             };
-            nodes.insert(this_node);
-            for edge in g.edges_directed(node, petgraph::EdgeDirection::Outgoing) {
-                if package_nodes {
-                    new_edges.insert((
-                        fg.base_node(edge.target()).expect("base node must exist"),
-                        this_node,
-                    ));
+            if depths.get(&next.0).is_none_or(|&d| next.1 < d) {
+                depths.insert(next.0, next.1);
+                if next.1 == depth {
+                    queue.push_front(next);
                 } else {
-                    edges.insert(edge.id());
+                    queue.push_back(next);
                 }
             }
         }
     }
 
+    let base = |node: NodeIndex| {
+        if package_nodes {
+            fg.base_node(node).expect("base node must exist")
+        } else {
+            node
+        }
+    };
+    let mut nodes = BTreeSet::new();
+    let mut edges = BTreeSet::new();
+    let mut new_edges = BTreeSet::new();
+    for (&node, &depth) in &depths {
+        nodes.insert(base(node));
+        for edge in g.edges_directed(node, petgraph::EdgeDirection::Outgoing) {
+            if !depths.contains_key(&edge.target()) || (crosses(edge) && !can_leave(node, depth)) {
+                continue;
+            }
+            if package_nodes {
+                new_edges.insert((base(node), base(edge.target())));
+            } else {
+                edges.insert(edge.id());
+            }
+        }
+    }
+
     if package_nodes {
         for (a, b) in new_edges {
-            let a = a.get_index(fg)?;
             if a != b {
                 let link = Link {
                     optional: false,
                     kinds: vec![DepKindInfo::NORMAL],
+                    weak: false,
                 };
-                edges.insert(fg.features.add_edge(b, a, link));
+                edges.insert(fg.features.add_edge(a, b, link));
             }
         }
     }
@@ -111,18 +234,46 @@ pub fn tree<'a>(
 
     fg.focus_nodes = Some(nodes);
     fg.focus_edges = Some(edges);
-    dump_fg(fg)
+    Ok(())
+}
+
+/// Which dependency kinds `explain` follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DevFilter {
+    /// all of them
+    #[default]
+    All,
+    /// skip dev-only dependencies
+    NoDev,
+    /// only reasons that involve a dev-only dependency
+    DevOnly,
 }
 
-pub fn explain<'a>(
-    fg: &'a mut FeatGraph<'a>,
+impl DevFilter {
+    /// Dependencies that can enable weak features
+    fn weak_links(self) -> Collect<'static> {
+        match self {
+            DevFilter::NoDev => Collect::NoDev,
+            DevFilter::All | DevFilter::DevOnly => Collect::DevTarget,
+        }
+    }
+}
+
+/// Focus on reverse dependencies of a given crate up to the workspace members
+///
+/// Workspace members are annotated with the way the crate is reachable from them in
+/// [`FeatGraph::reach`]
+pub fn explain(
+    fg: &mut FeatGraph,
     krate: &str,
-    feature: Option<&String>,
+    feature: Option<&str>,
     version: Option<&Version>,
     package_nodes: bool,
+    filter: DevFilter,
 ) -> anyhow::Result<()> {
-    fg.shrink_to_target()?;
-    let mut packages = collect_packages(fg, krate, feature, version);
+    fg.shrink_to_target(filter.weak_links())?;
+    mark_duplicates(fg);
+    let packages = collect_packages(fg, krate, feature, version)?;
 
     info!("Found {} matching package(s)", packages.len());
 
@@ -136,46 +287,111 @@ pub fn explain<'a>(
     } else {
         fg.focus_targets = Some(packages.iter().copied().collect::<BTreeSet<_>>());
     }
-    let g = EdgeFiltered::from_fn(Reversed(&fg.features), |e| {
-        !fg.features[e.source()].is_workspace()
-    });
 
-    let mut dfs = Dfs::new(&g, fg.root);
+    // walk reverse links keeping track of the way nodes are reached, every node
+    // can be visited once per kind of reachability
+    debug!("Collecting dependencies");
+    let mut visited = packages
+        .iter()
+        .map(|&ix| (ix, Reach::Normal))
+        .collect::<BTreeSet<_>>();
+    let mut stack = visited.iter().copied().collect::<Vec<_>>();
+    let mut links = Vec::new();
+    while let Some((node, reach)) = stack.pop() {
+        // don't go past the workspace members unless explaining one of them
+        if fg.features[node].is_workspace() && !packages.contains(&node) {
+            continue;
+        }
+        for edge in fg
+            .features
+            .edges_directed(node, petgraph::EdgeDirection::Incoming)
+        {
+            let link = edge.weight();
+            if filter == DevFilter::NoDev && link.is_dev_only() {
+                continue;
+            }
+            // workspace root is not interesting
+            if fg.features[edge.source()].fid().is_none() {
+                continue;
+            }
+            let next = (edge.source(), reach.max(Reach::of(link)));
+            links.push(((node, reach), edge.id(), next));
+            if visited.insert(next) {
+                stack.push(next);
+            }
+        }
+    }
 
-    let mut nodes = BTreeSet::new();
+    // keep only links leading to workspace members in an acceptable way
+    let accepted = |(node, reach): (NodeIndex, Reach)| {
+        fg.features[node].is_workspace()
+            && !packages.contains(&node)
+            && (filter != DevFilter::DevOnly || reach == Reach::Dev)
+    };
+    let mut useful = visited
+        .iter()
+        .copied()
+        .filter(|&state| accepted(state))
+        .collect::<BTreeSet<_>>();
+    loop {
+        let before = useful.len();
+        for &(from, _, to) in &links {
+            if useful.contains(&to) {
+                useful.insert(from);
+            }
+        }
+        if useful.len() == before {
+            break;
+        }
+    }
+
+    let mut reach = BTreeMap::new();
+    for &(node, r) in &useful {
+        if accepted((node, r)) {
+            let entry = reach.entry(node).or_insert(r);
+            *entry = r.min(*entry);
+        }
+    }
+
+    let base = |node: NodeIndex| {
+        if package_nodes {
+            fg.base_node(node).expect("base package node must exist")
+        } else {
+            node
+        }
+    };
+    let mut nodes = packages.iter().map(|&ix| base(ix)).collect::<BTreeSet<_>>();
     let mut edges = BTreeSet::new();
     let mut new_edges = BTreeSet::new();
-
-    debug!("Collecting dependencies");
-    while let Some(next) = packages.pop() {
-        dfs.move_to(next);
-        while let Some(node) = dfs.next(&g) {
-            let this_node = if package_nodes {
-                fg.base_node(node).expect("base package node must exist")
-            } else {
-                node
-            };
-            nodes.insert(this_node);
-            for edge in g.edges_directed(node, petgraph::EdgeDirection::Outgoing) {
-                if package_nodes {
-                    new_edges.insert((
-                        fg.base_node(edge.target()).expect("base node must exist"),
-                        this_node,
-                    ));
-                } else {
-                    edges.insert(edge.id());
-                }
-            }
+    for (from, edge, to) in links {
+        if !(useful.contains(&from) && useful.contains(&to)) {
+            continue;
+        }
+        nodes.insert(base(from.0));
+        nodes.insert(base(to.0));
+        if package_nodes {
+            new_edges.insert((base(to.0), base(from.0)));
+        } else {
+            edges.insert(edge);
         }
     }
 
+    let reach = reach.into_iter().map(|(node, r)| (base(node), r)).fold(
+        BTreeMap::new(),
+        |mut acc, (node, r)| {
+            let entry = acc.entry(node).or_insert(r);
+            *entry = r.min(*entry);
+            acc
+        },
+    );
+
     if package_nodes {
         for (a, b) in new_edges {
-            let a = a.get_index(fg)?;
             if a != b {
                 let link = Link {
                     optional: false,
                     kinds: vec![DepKindInfo::NORMAL],
+                    weak: false,
                 };
                 edges.insert(fg.features.add_edge(a, b, link));
             }
@@ -185,29 +401,375 @@ pub fn explain<'a>(
     info!("Done traversing");
     debug!("Found {} nodes and {} edges", nodes.len(), edges.len());
 
+    fg.reach = reach;
     fg.focus_nodes = Some(nodes);
     fg.focus_edges = Some(edges);
-    dump_fg(fg)
+    Ok(())
 }
 
-fn dump_fg(fg: &FeatGraph) -> anyhow::Result<()> {
-    #[cfg(feature = "spawn_xdot")]
-    {
-        let mut file = tempfile::NamedTempFile::new()?;
-        dot::render(fg, &mut file)?;
-        if std::process::Command::new("xdot")
-            .args([file.path()])
-            .output()
-            .is_err()
+/// Dependency chain from a workspace member to a crate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepPath {
+    /// workspace member the path starts from
+    pub member: String,
+    /// packages along the way, possibly with a feature, such as `reqwest:default-tls`
+    pub steps: Vec<String>,
+    /// kind of dependencies involved
+    pub reach: Reach,
+}
+
+/// Find up to `limit` shortest distinct dependency paths from each workspace member to a crate
+///
+/// Each path starts at a workspace member and ends at the crate, consecutive features of the
+/// same package are merged into a single step such as `reqwest:default-tls`. Paths are grouped
+/// by member, sorted by member name. Returns `None` if there's no such crate.
+pub fn paths(
+    fg: &mut FeatGraph,
+    krate: &str,
+    feature: Option<&str>,
+    version: Option<&Version>,
+    limit: usize,
+    filter: DevFilter,
+) -> anyhow::Result<Option<Vec<DepPath>>> {
+    fg.shrink_to_target(filter.weak_links())?;
+    let targets = collect_packages(fg, krate, feature, version)?;
+    info!("Found {} matching package(s)", targets.len());
+    if targets.is_empty() {
+        return Ok(None);
+    }
+
+    let mut members = fg.workspace_members.iter().copied().collect::<Vec<_>>();
+    members.sort_by(|a, b| a.package().name.cmp(&b.package().name));
+    let mut res = Vec::new();
+    for member in members {
+        res.extend(member_paths(fg, &targets, member, feature, limit, filter));
+    }
+    debug!("Found {} path(s)", res.len());
+    Ok(Some(res))
+}
+
+/// Shortest distinct paths from a single workspace member to any of the targets
+fn member_paths(
+    fg: &FeatGraph,
+    targets: &[NodeIndex],
+    member: Pid,
+    feature: Option<&str>,
+    limit: usize,
+    filter: DevFilter,
+) -> Vec<DepPath> {
+    // breadth first search over partial paths in reverse direction, each node is allowed to
+    // be expanded at most `limit` times - enough to produce `limit` shortest paths
+    let mut expanded = BTreeMap::<NodeIndex, usize>::new();
+    let mut queue = targets.iter().map(|&ix| vec![ix]).collect::<VecDeque<_>>();
+    let mut res = Vec::new();
+    while let Some(path) = queue.pop_front() {
+        if res.len() >= limit {
+            break;
+        }
+        let last = *path.last().expect("paths are never empty");
+        if path.len() > 1 && fg.features[last].is_workspace() {
+            // paths don't go through other workspace members
+            if fg.features[last].fid().map(|fid| fid.pid) != Some(member) {
+                continue;
+            }
+            let path = DepPath {
+                member: member.package().name.clone(),
+                steps: path_steps(fg, &path, feature),
+                reach: path_reach(fg, &path),
+            };
+            // a member matching the crate itself isn't a dependency path
+            if path.steps.len() > 1
+                && (filter != DevFilter::DevOnly || path.reach == Reach::Dev)
+                && !res.contains(&path)
+            {
+                res.push(path);
+            }
+            continue;
+        }
+        let count = expanded.entry(last).or_default();
+        if *count >= limit {
+            continue;
+        }
+        *count += 1;
+        for edge in fg
+            .features
+            .edges_directed(last, petgraph::EdgeDirection::Incoming)
         {
-            eprintln!("xdot not found, you can either install it or hackerman with \"spawn_xdot\" feature disabled");
+            let next = edge.source();
+            if filter == DevFilter::NoDev && edge.weight().is_dev_only() {
+                continue;
+            }
+            if fg.features[next].fid().is_some() && !path.contains(&next) {
+                let mut path = path.clone();
+                path.push(next);
+                queue.push_back(path);
+            }
         }
     }
+    res
+}
 
-    #[cfg(not(feature = "spawn_xdot"))]
-    {
-        dot::render(&graph, &mut std::io::stdout())?;
+/// Worst link kind along a reversed path, parallel links pick the best kind
+fn path_reach(fg: &FeatGraph, path: &[NodeIndex]) -> Reach {
+    path.windows(2)
+        .filter_map(|pair| {
+            fg.features
+                .edges_connecting(pair[1], pair[0])
+                .map(|edge| Reach::of(edge.weight()))
+                .min()
+        })
+        .max()
+        .unwrap_or(Reach::Normal)
+}
+
+/// Render a reversed path, going from a workspace member to the crate
+///
+/// Features of the crate itself only matter if they were asked for
+fn path_steps(fg: &FeatGraph, path: &[NodeIndex], feature: Option<&str>) -> Vec<String> {
+    let mut steps = Vec::<(Pid, Option<&str>)>::new();
+    for fid in path.iter().rev().filter_map(|&ix| fg.features[ix].fid()) {
+        let name = match fid.dep {
+            Feat::Named(name) if name != "default" => Some(name),
+            _ => None,
+        };
+        match steps.last_mut() {
+            Some((pid, feat)) if *pid == fid.pid => {
+                *feat = name.or(*feat);
+            }
+            _ => steps.push((fid.pid, name)),
+        }
+    }
+    if let Some((_, feat)) = steps.last_mut() {
+        *feat = feature;
     }
+    steps
+        .into_iter()
+        .map(|(pid, feat)| {
+            let name = &pid.package().name;
+            match feat {
+                Some(feat) => format!("{name}:{feat}"),
+                None => name.to_string(),
+            }
+        })
+        .collect()
+}
 
-    Ok(())
+/// Owned snapshot of a focused part of the feature graph produced by `explain` or `tree`
+#[derive(Debug, Clone)]
+pub struct ExplainResult {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+    /// graph in graphviz dot format
+    pub dot: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    /// unique node identifier, used by edges
+    pub id: usize,
+    /// package this node belongs to, `None` for the workspace root
+    pub package: Option<PackageId>,
+    /// named feature, `None` for the package itself
+    pub feature: Option<String>,
+    /// package name and version
+    pub label: String,
+    /// node belongs to a workspace member
+    pub workspace: bool,
+    /// node is the one being explained
+    pub target: bool,
+    /// node is part of a dependency cycle
+    pub cycle: bool,
+    /// node belongs to a crate present in more than one version
+    pub duplicate: bool,
+    /// node belongs to a procedural macro crate
+    pub proc_macro: bool,
+    /// node belongs to a crate with a build script
+    pub build_script: bool,
+    /// native library the crate links to
+    pub links: Option<String>,
+    /// how explained crates are reachable from this workspace member
+    pub reach: Option<Reach>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub optional: bool,
+    pub dev_only: bool,
+    pub build_only: bool,
+    /// link comes from a weak dependency feature such as `"rgb?/serde"`
+    pub weak: bool,
+    /// dependency kinds, platforms and renames, only present when edge labels are requested
+    pub label: Option<String>,
+}
+
+impl ExplainResult {
+    /// Take a snapshot of nodes and edges focused by `explain` or `tree`
+    pub fn from_focus(fg: &FeatGraph) -> anyhow::Result<Self> {
+        let dot = fg.render_dot()?;
+
+        let nodes = fg
+            .nodes()
+            .iter()
+            .map(|&ix| {
+                let fid = fg.features[ix].fid();
+                Node {
+                    id: ix.index(),
+                    package: fg.features[ix].package_id().cloned(),
+                    feature: fid.and_then(|fid| match fid.dep {
+                        Feat::Base => None,
+                        Feat::Named(name) => Some(name.to_string()),
+                    }),
+                    label: fg.package_label(ix),
+                    workspace: fid.is_none_or(|fid| fg.workspace_members.contains(&fid.pid)),
+                    target: fg
+                        .focus_targets
+                        .as_ref()
+                        .is_some_and(|targets| targets.contains(&ix)),
+                    cycle: fg.in_cycle(ix),
+                    duplicate: fg.is_dupe(ix),
+                    proc_macro: fg.is_proc_macro(ix),
+                    build_script: fg.has_build_script(ix),
+                    links: fg.links(ix).map(String::from),
+                    reach: fg.reach.get(&ix).copied(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let edges = fg
+            .edges()
+            .iter()
+            .map(|&ix| {
+                let link = &fg.features[ix];
+                Edge {
+                    from: fg.source(&ix).index(),
+                    to: fg.target(&ix).index(),
+                    optional: link.optional,
+                    dev_only: link.is_dev_only(),
+                    build_only: link.is_build_only(),
+                    weak: link.weak,
+                    label: Some(link.label()).filter(|l| fg.edge_labels && !l.is_empty()),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Self { nodes, edges, dot })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::process_fg_with;
+
+    #[test]
+    fn paths_stop_at_workspace_members() -> anyhow::Result<()> {
+        process_fg_with(11, |fg| {
+            let found = paths(fg, "gamma", None, None, 10, DevFilter::All)?;
+            let expected = DepPath {
+                member: "beta".to_string(),
+                steps: vec!["beta".to_string(), "gamma".to_string()],
+                reach: Reach::Normal,
+            };
+            assert_eq!(found, Some(vec![expected]));
+            assert_eq!(paths(fg, "delta", None, None, 10, DevFilter::All)?, None);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn paths_limit_per_member() -> anyhow::Result<()> {
+        process_fg_with(5, |fg| {
+            let found = paths(fg, "gamma", None, None, 1, DevFilter::All)?.unwrap_or_default();
+            let found = found
+                .iter()
+                .map(|path| (path.member.as_str(), path.steps.join(" -> ")))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                found,
+                [
+                    ("alpha", "alpha:one -> gamma".to_string()),
+                    ("beta", "beta -> gamma".to_string())
+                ]
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn crate_patterns() -> anyhow::Result<()> {
+        assert!(name_pattern("openssl*")?.is_match("openssl-sys"));
+        assert!(!name_pattern("openssl*")?.is_match("native-tls"));
+        assert!(name_pattern("ring")?.is_match("ring"));
+        assert!(!name_pattern("ring")?.is_match("ring-compat"));
+        assert!(name_pattern("/^(ring|rustls)$/")?.is_match("rustls"));
+        assert!(name_pattern("/(/").is_err());
+        assert_eq!(
+            split_specs("/^a{1,3}$/,beta,/^(c|d)$/"),
+            ["/^a{1,3}$/", "beta", "/^(c|d)$/"]
+        );
+        assert_eq!(split_specs("alpha,/x{2,}/"), ["alpha", "/x{2,}/"]);
+
+        for pattern in ["alph?,/^gam/", "/^[a-z]{4,5}a$/"] {
+            process_fg_with(11, |fg| {
+                explain(fg, pattern, None, None, false, DevFilter::All)?;
+                assert_eq!(fg.focus_targets.as_ref().map(BTreeSet::len), Some(2));
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn explain_dev_reasons() -> anyhow::Result<()> {
+        process_fg_with(11, |fg| {
+            explain(fg, "alpha", None, None, true, DevFilter::All)?;
+            let reach = fg
+                .reach
+                .iter()
+                .map(|(&ix, &r)| (fg.package_label(ix), r))
+                .collect::<Vec<_>>();
+            assert_eq!(reach, [("beta".to_string(), Reach::Dev)]);
+            Ok(())
+        })?;
+
+        // package nodes mode adds new links to the graph
+        process_fg_with(11, |fg| {
+            explain(fg, "alpha", None, None, true, DevFilter::NoDev)?;
+            assert!(fg.reach.is_empty());
+            assert_eq!(fg.focus_nodes.as_ref().map(BTreeSet::len), Some(1));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn tree_depth_and_pruning() -> anyhow::Result<()> {
+        let packages = |filter: TreeFilter| -> anyhow::Result<BTreeSet<String>> {
+            process_fg_with(11, |fg| {
+                tree(fg, Some("alpha"), None, None, false, &filter)?;
+                Ok(fg
+                    .focus_nodes
+                    .iter()
+                    .flatten()
+                    .map(|&ix| fg.package_label(ix))
+                    .collect())
+            })
+        };
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+
+        let all = packages(TreeFilter::default())?;
+        assert_eq!(all, names(&["alpha", "beta", "gamma"]));
+        let shallow = packages(TreeFilter {
+            depth: Some(1),
+            ..TreeFilter::default()
+        })?;
+        assert_eq!(shallow, names(&["alpha", "beta"]));
+        let pruned = packages(TreeFilter {
+            prune: vec!["beta".to_string()],
+            ..TreeFilter::default()
+        })?;
+        assert_eq!(pruned, names(&["alpha", "beta"]));
+        Ok(())
+    }
 }
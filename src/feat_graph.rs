@@ -1,5 +1,5 @@
 use crate::hack::Collect;
-use crate::metadata::{DepKindInfo, Link};
+use crate::metadata::{DepKindInfo, Link, Reach};
 use cargo_metadata::{Metadata, Package, PackageId, Source};
 use cargo_platform::Cfg;
 use dot::{GraphWalk, Labeller};
@@ -9,7 +9,7 @@ use petgraph::Graph;
 use std::borrow::Cow;
 use std::collections::{BTreeMap, BTreeSet};
 use std::ops::Index;
-use tracing::{debug, error, info, trace};
+use tracing::{debug, info, trace};
 
 #[derive(Copy, Clone, Ord, PartialEq, Eq, PartialOrd, Debug)]
 /// An node for feature graph
@@ -85,6 +85,15 @@ pub struct FeatGraph<'a> {
     pub focus_nodes: Option<BTreeSet<NodeIndex>>,
     pub focus_edges: Option<BTreeSet<EdgeIndex>>,
     pub focus_targets: Option<BTreeSet<NodeIndex>>,
+    /// how explained crates are reachable from workspace members
+    pub reach: BTreeMap<NodeIndex, Reach>,
+    /// packages present in more than one version, highlighted in graphs
+    pub dupes: BTreeSet<Pid<'a>>,
+    /// label links with dependency kinds, platforms and renames
+    pub edge_labels: bool,
+
+    /// features that are part of a dependency cycle, along with the cycle they belong to
+    pub cycles: BTreeMap<Fid<'a>, usize>,
 }
 
 impl<'a> Index<Pid<'a>> for FeatGraph<'a> {
@@ -118,6 +127,168 @@ impl<'a> FeatGraph<'a> {
         })
     }
 
+    /// check if node is a part of some dependency cycle
+    #[must_use]
+    pub fn in_cycle(&self, node: NodeIndex) -> bool {
+        self.features[node]
+            .fid()
+            .is_some_and(|fid| self.cycles.contains_key(&fid))
+    }
+
+    /// check if node belongs to a package present in more than one version
+    #[must_use]
+    pub fn is_dupe(&self, node: NodeIndex) -> bool {
+        self.features[node]
+            .pid()
+            .is_some_and(|pid| self.dupes.contains(&pid))
+    }
+
+    /// Keep only parts of the focused graph going through nodes matching a predicate
+    ///
+    /// Nodes leading to matching nodes are kept, nodes reachable from them are kept only
+    /// with `descendants`
+    pub fn focus_through<F>(&mut self, matches: F, descendants: bool)
+    where
+        F: Fn(&Self, NodeIndex) -> bool,
+    {
+        let nodes = self
+            .focus_nodes
+            .clone()
+            .unwrap_or_else(|| self.features.node_indices().collect());
+        let edges = self
+            .focus_edges
+            .clone()
+            .unwrap_or_else(|| self.features.edge_indices().collect());
+        let seeds = nodes
+            .iter()
+            .copied()
+            .filter(|&ix| matches(self, ix))
+            .collect::<Vec<_>>();
+
+        let mut keep = seeds.iter().copied().collect::<BTreeSet<_>>();
+        let mut dirs = vec![petgraph::EdgeDirection::Incoming];
+        if descendants {
+            dirs.push(petgraph::EdgeDirection::Outgoing);
+        }
+        for dir in dirs {
+            let mut visited = seeds.iter().copied().collect::<BTreeSet<_>>();
+            let mut todo = seeds.clone();
+            while let Some(node) = todo.pop() {
+                for edge in self.features.edges_directed(node, dir) {
+                    let next = match dir {
+                        petgraph::EdgeDirection::Incoming => edge.source(),
+                        petgraph::EdgeDirection::Outgoing => edge.target(),
+                    };
+                    if edges.contains(&edge.id()) && nodes.contains(&next) && visited.insert(next) {
+                        keep.insert(next);
+                        todo.push(next);
+                    }
+                }
+            }
+        }
+
+        let edges = edges
+            .into_iter()
+            .filter(|&ix| {
+                self.features
+                    .edge_endpoints(ix)
+                    .is_some_and(|(a, b)| keep.contains(&a) && keep.contains(&b))
+            })
+            .collect();
+        self.focus_nodes = Some(keep);
+        self.focus_edges = Some(edges);
+    }
+
+    fn has_target_kind(&self, node: NodeIndex, kind: &str) -> bool {
+        self.features[node].pid().is_some_and(|pid| {
+            pid.package()
+                .targets
+                .iter()
+                .any(|t| t.kind.iter().any(|k| k == kind))
+        })
+    }
+
+    /// check if node belongs to a procedural macro crate
+    #[must_use]
+    pub fn is_proc_macro(&self, node: NodeIndex) -> bool {
+        self.has_target_kind(node, "proc-macro")
+    }
+
+    /// check if node belongs to a crate with a build script
+    #[must_use]
+    pub fn has_build_script(&self, node: NodeIndex) -> bool {
+        self.has_target_kind(node, "custom-build")
+    }
+
+    /// native library the package links to, as given by `links` manifest key
+    #[must_use]
+    pub fn links(&self, node: NodeIndex) -> Option<&'a str> {
+        self.features[node].pid()?.package().links.as_deref()
+    }
+
+    /// Proc macro, build script and native library markers, comma separated
+    #[must_use]
+    pub fn package_markers(&self, node: NodeIndex) -> Option<String> {
+        package_markers(
+            self.is_proc_macro(node),
+            self.has_build_script(node),
+            self.links(node),
+        )
+    }
+
+    /// Package name along with version or `git` for git dependencies, `root` for the workspace root
+    ///
+    /// Duplicated packages always include the version
+    #[must_use]
+    pub fn package_label(&self, node: NodeIndex) -> String {
+        let fid = match self.features[node].fid() {
+            Some(fid) => fid,
+            None => return String::from("root"),
+        };
+        let package = fid.pid.package();
+        let mut fmt = package.name.clone();
+        let git = package
+            .source
+            .as_ref()
+            .is_some_and(|src| src.repr.starts_with("git"));
+        if (package.source.is_some() && !git) || self.dupes.contains(&fid.pid) {
+            fmt.push_str(&format!(" {}", package.version));
+        }
+        if git {
+            fmt.push_str(" git");
+        }
+        fmt
+    }
+
+    /// Render focused part of the graph in dot format, each cycle is drawn as a cluster
+    pub fn render_dot(&self) -> anyhow::Result<String> {
+        let mut out = Vec::new();
+        dot::render(self, &mut out)?;
+        let mut out = String::from_utf8(out)?;
+
+        let mut clusters = BTreeMap::<usize, Vec<NodeIndex>>::new();
+        for node in self.nodes().iter() {
+            if let Some(cycle) = self.features[*node].fid().and_then(|f| self.cycles.get(&f)) {
+                clusters.entry(*cycle).or_default().push(*node);
+            }
+        }
+        // dot crate has no support for subgraphs, they go right before the closing brace
+        let mut subgraphs = String::new();
+        for (cycle, nodes) in clusters {
+            subgraphs.push_str(&format!(
+                "    subgraph cluster_{cycle} {{\n        style=dashed;\n        color=orange;\n"
+            ));
+            for node in nodes {
+                subgraphs.push_str(&format!("        {};\n", self.node_id(&node).name()));
+            }
+            subgraphs.push_str("    }\n");
+        }
+        if let Some(end) = out.rfind('}') {
+            out.insert_str(end, &subgraphs);
+        }
+        Ok(out)
+    }
+
     /// for any node find node for the base of this package
     #[must_use]
     pub fn base_node(&self, node: NodeIndex) -> Option<NodeIndex> {
@@ -126,12 +297,42 @@ impl<'a> FeatGraph<'a> {
             .copied()
     }
 
-    pub fn shrink_to_target(&mut self) -> anyhow::Result<()> {
+    /// Drop everything not used on the current target
+    ///
+    /// `filter` decides which dependencies can enable weak features such as `"rgb?/serde"`,
+    /// use [`Collect::NoDev`] to ignore ones enabled by dev dependencies only
+    pub fn shrink_to_target(&mut self, filter: Collect) -> anyhow::Result<()> {
         info!("Shrinking to current target");
+        loop {
+            let enabled = self.reachable_on_target(filter);
+            let weak_links = self.active_triggers(&enabled, filter);
+            if weak_links.is_empty() {
+                break;
+            }
+            debug!("Weak dependencies add {} new links", weak_links.len());
+            for (feature, weak_feat) in weak_links {
+                let link = Link {
+                    optional: true,
+                    kinds: vec![DepKindInfo::NORMAL],
+                    weak: true,
+                };
+                let weak_feat = self.fid_index(weak_feat);
+                self.features.add_edge(feature, weak_feat, link);
+            }
+        }
+
+        let this = self.reachable_on_target(Collect::DevTarget);
+        self.features.retain_nodes(|_, ix| this.contains(&ix));
+        self.rebuild_cache()?;
+
+        Ok(())
+    }
+
+    fn reachable_on_target(&self, filter: Collect) -> BTreeSet<NodeIndex> {
         let g = EdgeFiltered::from_fn(&self.features, |e| {
             e.weight().satisfies(
                 self.features[e.source()],
-                Collect::DevTarget,
+                filter,
                 &self.platforms,
                 &self.cfgs,
             )
@@ -141,11 +342,44 @@ impl<'a> FeatGraph<'a> {
         while let Some(ix) = dfs.next(&g) {
             this.insert(ix);
         }
+        this
+    }
 
-        self.features.retain_nodes(|_, ix| this.contains(&ix));
-        self.rebuild_cache()?;
-
-        Ok(())
+    /// Weak dependency links that are not present in the graph yet but should be: both
+    /// triggering feature and weak dependency are reachable
+    pub(crate) fn active_triggers(
+        &self,
+        reachable: &BTreeSet<NodeIndex>,
+        filter: Collect,
+    ) -> Vec<(NodeIndex, Fid<'a>)> {
+        let mut res = Vec::new();
+        for trigger in self.triggers.values().flatten() {
+            let feature = match self.fids.get(&trigger.feature) {
+                Some(&ix) if reachable.contains(&ix) => ix,
+                _ => continue,
+            };
+            if let Some(&weak_feat) = self.fids.get(&trigger.weak_feat) {
+                if self.features.find_edge(feature, weak_feat).is_some() {
+                    continue;
+                }
+            }
+            let weak_dep_enabled = reachable.iter().any(|&ix| {
+                self.features[ix].pid() == Some(trigger.package)
+                    && self.features.edges(ix).any(|e| {
+                        self.features[e.target()].pid() == Some(trigger.weak_dep)
+                            && e.weight().satisfies(
+                                self.features[ix],
+                                filter,
+                                &self.platforms,
+                                &self.cfgs,
+                            )
+                    })
+            });
+            if weak_dep_enabled {
+                res.push((feature, trigger.weak_feat));
+            }
+        }
+        res
     }
 
     pub fn init(
@@ -186,6 +420,10 @@ impl<'a> FeatGraph<'a> {
             focus_nodes: None,
             focus_edges: None,
             focus_targets: None,
+            reach: BTreeMap::new(),
+            dupes: BTreeSet::new(),
+            edge_labels: false,
+            cycles: BTreeMap::new(),
         };
 
         for (ix, package) in meta.packages.iter().enumerate() {
@@ -205,6 +443,7 @@ impl<'a> FeatGraph<'a> {
             info!("Optimization pass: transitive reduction");
             self.transitive_reduction();
         }
+        self.find_cycles();
 
         self.rebuild_cache()?;
         Ok(())
@@ -227,25 +466,42 @@ impl<'a> FeatGraph<'a> {
     }
 
     fn transitive_reduction(&mut self) {
-        use petgraph::algo::tred::dag_to_toposorted_adjacency_list;
+        use petgraph::algo::tred::{
+            dag_to_toposorted_adjacency_list, dag_transitive_reduction_closure,
+        };
         let graph = &mut self.features;
         let before = graph.edge_count();
-        let toposort = match petgraph::algo::toposort(&*graph, None) {
-            Ok(t) => t,
-            Err(err) => {
-                error!("Cyclic dependencies are detected {err:?}, skipping transitive reduction");
-                return;
+
+        // dev dependencies can introduce cycles while reduction only works on a DAG,
+        // so strongly connected components are condensed into single nodes first
+        let sccs = petgraph::algo::tarjan_scc(&*graph);
+        let mut component = vec![0; graph.node_count()];
+        let mut condensed = Graph::<(), ()>::with_capacity(sccs.len(), 0);
+        for (ix, scc) in sccs.iter().enumerate() {
+            condensed.add_node(());
+            for node in scc {
+                component[node.index()] = ix;
             }
-        };
+        }
+        for edge in graph.edge_references() {
+            let a = component[edge.source().index()];
+            let b = component[edge.target().index()];
+            if a != b {
+                condensed.update_edge(NodeIndex::new(a), NodeIndex::new(b), ());
+            }
+        }
 
+        // tarjan_scc gives components in reverse topological order
+        let toposort = condensed.node_indices().rev().collect::<Vec<_>>();
         let (adj_list, revmap) =
-            dag_to_toposorted_adjacency_list::<_, NodeIndex>(&*graph, &toposort);
-        let (reduction, _closure) =
-            petgraph::algo::tred::dag_transitive_reduction_closure(&adj_list);
+            dag_to_toposorted_adjacency_list::<_, NodeIndex>(&condensed, &toposort);
+        let (reduction, _closure) = dag_transitive_reduction_closure(&adj_list);
 
+        // edges inside of a cycle are kept as is
         graph.retain_edges(|x, y| {
             if let Some((f, t)) = x.edge_endpoints(y) {
-                reduction.contains_edge(revmap[f.index()], revmap[t.index()])
+                let (f, t) = (component[f.index()], component[t.index()]);
+                f == t || reduction.contains_edge(revmap[f], revmap[t])
             } else {
                 false
             }
@@ -254,6 +510,20 @@ impl<'a> FeatGraph<'a> {
         debug!("Transitive reduction, edges {before} -> {after}");
     }
 
+    /// Group features that depend on each other, usually through dev dependencies
+    fn find_cycles(&mut self) {
+        self.cycles.clear();
+        let sccs = petgraph::algo::tarjan_scc(&self.features);
+        for (ix, scc) in sccs.iter().enumerate().filter(|(_, scc)| scc.len() > 1) {
+            debug!("Cyclic dependency between {} features", scc.len());
+            for &node in scc {
+                if let Some(fid) = self.features[node].fid() {
+                    self.cycles.insert(fid, ix);
+                }
+            }
+        }
+    }
+
     /// Remove features not used by the workspace directly or indirectly
     ///
     /// should only be used for displaying
@@ -438,10 +708,12 @@ impl<'a> FeatGraph<'a> {
                 old_link.kinds.push(kind);
             }
             old_link.optional &= optional;
+            old_link.weak = false;
         } else {
             let link = Link {
                 optional,
                 kinds: vec![kind],
+                weak: false,
             };
             self.features.add_edge(a, b, link);
         }
@@ -449,6 +721,28 @@ impl<'a> FeatGraph<'a> {
     }
 }
 
+pub(crate) fn package_markers(
+    proc_macro: bool,
+    build_script: bool,
+    links: Option<&str>,
+) -> Option<String> {
+    let mut markers = Vec::new();
+    if proc_macro {
+        markers.push(String::from("proc-macro"));
+    }
+    if build_script {
+        markers.push(String::from("build.rs"));
+    }
+    if let Some(links) = links {
+        markers.push(format!("links = {links}"));
+    }
+    if markers.is_empty() {
+        None
+    } else {
+        Some(markers.join(", "))
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct Pid<'a>(usize, &'a Metadata);
 
@@ -582,45 +876,44 @@ impl<'a> Labeller<'a, NodeIndex, EdgeIndex> for FeatGraph<'a> {
     fn node_shape(&'a self, node: &NodeIndex) -> Option<dot::LabelText<'a>> {
         let fid = self.features[*node].fid()?;
         match fid.dep {
+            Feat::Base if self.is_proc_macro(*node) => Some(dot::LabelText::label("doubleoctagon")),
+            Feat::Base if self.has_build_script(*node) => {
+                Some(dot::LabelText::label("tripleoctagon"))
+            }
             Feat::Base => Some(dot::LabelText::label("octagon")),
             Feat::Named(_) => None,
         }
     }
 
     fn node_label(&'a self, n: &NodeIndex) -> dot::LabelText<'a> {
-        let mut fmt = String::new();
-        match self.features[*n].fid() {
-            Some(fid) => {
-                let package = fid.pid.package();
-                fmt.push_str(&package.name);
-
-                match package.source.as_ref() {
-                    Some(src) => {
-                        if src.repr.starts_with("git") {
-                            fmt.push_str(" git");
-                        } else {
-                            fmt.push_str(&format!(" {}", package.version));
-                        }
-                    }
-                    None => {}
-                }
-                match fid.dep {
-                    Feat::Base => {}
-                    Feat::Named(name) => {
-                        fmt.push('\n');
-                        fmt.push_str(name);
-                    }
+        let mut fmt = self.package_label(*n);
+        match self.features[*n].fid().map(|fid| fid.dep) {
+            Some(Feat::Named(name)) => {
+                fmt.push('\n');
+                fmt.push_str(name);
+            }
+            Some(Feat::Base) => {
+                if let Some(markers) = self.package_markers(*n) {
+                    fmt.push('\n');
+                    fmt.push_str(&markers);
                 }
-
-                dot::LabelText::LabelStr(fmt.into())
             }
-            None => dot::LabelText::LabelStr("root".into()),
+            None => {}
+        }
+        match self.reach.get(n) {
+            Some(Reach::Build) => fmt.push_str("\n(build only)"),
+            Some(Reach::Dev) => fmt.push_str("\n(dev only)"),
+            Some(Reach::Normal) | None => {}
         }
+        dot::LabelText::LabelStr(fmt.into())
     }
 
     fn edge_label(&'a self, e: &EdgeIndex) -> dot::LabelText<'a> {
-        let _ = e;
-        dot::LabelText::LabelStr("".into())
+        if self.edge_labels {
+            dot::LabelText::LabelStr(self.features[*e].label().into())
+        } else {
+            dot::LabelText::LabelStr("".into())
+        }
     }
 
     fn node_style(&'a self, n: &NodeIndex) -> dot::Style {
@@ -636,10 +929,21 @@ impl<'a> Labeller<'a, NodeIndex, EdgeIndex> for FeatGraph<'a> {
     }
 
     fn node_color(&'a self, node: &NodeIndex) -> Option<dot::LabelText<'a>> {
-        self.focus_targets
-            .as_ref()?
-            .contains(node)
-            .then(|| dot::LabelText::LabelStr("pink".into()))
+        if self
+            .focus_targets
+            .as_ref()
+            .is_some_and(|targets| targets.contains(node))
+        {
+            Some(dot::LabelText::LabelStr("pink".into()))
+        } else if self.in_cycle(*node) {
+            Some(dot::LabelText::LabelStr("orange".into()))
+        } else if self.is_dupe(*node) {
+            Some(dot::LabelText::LabelStr("gold".into()))
+        } else if self.links(*node).is_some() {
+            Some(dot::LabelText::LabelStr("lightblue".into()))
+        } else {
+            None
+        }
     }
 
     fn edge_end_arrow(&'a self, _e: &EdgeIndex) -> dot::Arrow {
@@ -651,15 +955,27 @@ impl<'a> Labeller<'a, NodeIndex, EdgeIndex> for FeatGraph<'a> {
     }
 
     fn edge_style(&'a self, e: &EdgeIndex) -> dot::Style {
-        if self.features[*e].is_dev_only() {
+        if self.features[*e].weak {
+            dot::Style::Dotted
+        } else if self.features[*e].is_dev_only() {
             dot::Style::Dashed
+        } else if self.features[*e].is_build_only() {
+            dot::Style::Bold
         } else {
             dot::Style::None
         }
     }
 
     fn edge_color(&'a self, e: &EdgeIndex) -> Option<dot::LabelText<'a>> {
-        if self.features[*e].optional {
+        let (a, b) = self.features.edge_endpoints(*e)?;
+        let cycle = |node: NodeIndex| {
+            self.features[node]
+                .fid()
+                .and_then(|fid| self.cycles.get(&fid))
+        };
+        if cycle(a).is_some() && cycle(a) == cycle(b) {
+            Some(dot::LabelText::label("orange"))
+        } else if self.features[*e].optional {
             Some(dot::LabelText::label("grey"))
         } else {
             Some(dot::LabelText::label("black"))
@@ -744,6 +1060,7 @@ impl<'a> From<&'a str> for FeatTarget<'a> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::test_util::process_fg_with;
     #[test]
     fn feat_target() {
         use FeatTarget::*;
@@ -770,26 +1087,6 @@ mod test {
         );
     }
 
-    fn get_demo_meta(ix: usize) -> anyhow::Result<Metadata> {
-        let path = format!(
-            "{}/test_workspaces/{ix}/metadata.json",
-            env!("CARGO_MANIFEST_DIR")
-        );
-        let data = std::fs::read_to_string(path)?;
-        Ok(cargo_metadata::MetadataCommand::parse(data)?)
-    }
-
-    fn process_fg_with<F>(ix: usize, op: F) -> anyhow::Result<()>
-    where
-        F: FnOnce(&mut FeatGraph) -> anyhow::Result<()>,
-    {
-        let meta = get_demo_meta(ix)?;
-        let platform = target_spec::Platform::current()?;
-        let triplets = vec![platform.triple_str()];
-        let mut fg = FeatGraph::init(&meta, triplets, Vec::new())?;
-        op(&mut fg)
-    }
-
     #[test]
     fn metadata_snapshot_2() -> anyhow::Result<()> {
         process_fg_with(2, |_| Ok(()))?;
@@ -816,6 +1113,46 @@ mod test {
             Ok(())
         })
     }
+
+    #[test]
+    fn weak_triggers_become_links() -> anyhow::Result<()> {
+        process_fg_with(5, |fg| {
+            fg.shrink_to_target(Collect::DevTarget)?;
+            let weak = fg
+                .features
+                .edge_references()
+                .filter(|e| e.weight().weak)
+                .map(|e| (fg.features[e.source()], fg.features[e.target()]))
+                .collect::<Vec<_>>();
+            assert_eq!(weak.len(), 1);
+            let (from, to) = weak[0];
+            assert_eq!(from.fid().unwrap().dep, Feat::Named("one"));
+            assert_eq!(to.fid().unwrap().dep, Feat::Named("one"));
+            assert_ne!(from.pid(), to.pid());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn transitive_reduction_with_cycles() -> anyhow::Result<()> {
+        process_fg_with(11, |fg| {
+            let before = fg.features.edge_count();
+            fg.optimize(false)?;
+            assert!(fg.features.edge_count() < before);
+            assert_eq!(fg.cycles.len(), 2);
+            let dot = fg.render_dot()?;
+            let cluster = dot
+                .find("subgraph cluster_")
+                .expect("cycle is drawn as a cluster");
+            assert!(cluster < dot.rfind('}').unwrap());
+            Ok(())
+        })?;
+        process_fg_with(11, |fg| {
+            fg.optimize(true)?;
+            assert_eq!(fg.cycles.len(), 2);
+            Ok(())
+        })
+    }
 }
 impl Fid<'_> {
     #[must_use]
@@ -4,10 +4,8 @@ use crate::{
     feat_graph::{Feat, FeatGraph, Pid},
     metadata::DepKindInfo,
     source::ChangePackage,
-    toml::set_dependencies,
 };
-use cargo_metadata::Metadata;
-use cargo_platform::Cfg;
+use cargo_metadata::{camino::Utf8PathBuf, Version};
 use petgraph::{
     graph::NodeIndex,
     visit::{
@@ -18,85 +16,76 @@ use petgraph::{
 use std::collections::{BTreeMap, BTreeSet};
 use tracing::{debug, info, trace, warn};
 
-fn force_config(var: &mut bool, name: &str, meta: &serde_json::Value) -> Option<()> {
+pub(crate) fn force_config(var: &mut bool, name: &str, meta: &serde_json::Value) -> Option<()> {
     *var = meta.get("hackerman")?.get(name)?.as_bool()?;
     Some(())
 }
 
-pub fn hack(
-    dry: bool,
-    mut lock: bool,
-    mut no_dev: bool,
-    meta: &Metadata,
-    triplets: Vec<&str>,
-    cfgs: Vec<Cfg>,
-) -> anyhow::Result<()> {
-    force_config(&mut lock, "lock", &meta.workspace_metadata);
-    force_config(&mut no_dev, "no-dev", &meta.workspace_metadata);
-
-    let mut fg = FeatGraph::init(meta, triplets, cfgs)?;
-    let changeset = get_changeset(&mut fg, no_dev)?;
-    let has_changes = !changeset.is_empty();
-
-    if dry {
-        if changeset.is_empty() {
-            println!("Features are unified as is");
-            return Ok(());
-        }
-        println!("Hackerman would like to set those features for following packets:");
+/// Changes required to unify features across the workspace
+#[derive(Debug, Default)]
+pub struct Changeset {
+    /// workspace members that need changes, members with no changes are omitted
+    pub members: Vec<MemberChanges>,
+}
+
+/// Dependencies that should be updated for a single workspace member
+#[derive(Debug)]
+pub struct MemberChanges {
+    pub name: String,
+    pub version: Version,
+    pub manifest_path: Utf8PathBuf,
+    /// sorted by dependency name
+    pub changes: Vec<ChangePackage>,
+}
+
+impl Changeset {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
     }
+}
 
-    for (member, changes) in changeset {
-        let mut changeset = changes
+/// Calculate changes required to unify features across the workspace
+pub fn changeset(fg: &mut FeatGraph, no_dev: bool) -> anyhow::Result<Changeset> {
+    let mut members = Vec::new();
+    for (member, changes) in get_changeset(fg, no_dev)? {
+        let mut changes = changes
             .into_iter()
             .map(|(dep, ty, rename, feats)| ChangePackage::make(member, dep, ty, rename, feats))
             .collect::<anyhow::Result<Vec<_>>>()?;
-
-        if dry {
-            changeset.sort_by(|a, b| a.name.cmp(&b.name));
-            let path = &member.package().manifest_path;
-            println!("{path}");
-            for change in changeset {
-                let t = match change.ty {
-                    Ty::Dev => "dev ",
-                    Ty::Norm => "",
-                };
-                println!("\t{} {}: {t}{:?}", change.name, change.source, change.feats);
-            }
-        } else {
-            let path = &member.package().manifest_path;
-            set_dependencies(path, lock, &changeset)?;
-        }
-    }
-
-    if dry && has_changes {
-        anyhow::bail!("Features are not unified");
+        changes.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let package = member.package();
+        members.push(MemberChanges {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            manifest_path: package.manifest_path.clone(),
+            changes,
+        });
     }
-
-    Ok(())
+    Ok(Changeset { members })
 }
 
 type FeatChanges<'a> = BTreeMap<Pid<'a>, Vec<(Pid<'a>, Ty, bool, BTreeSet<String>)>>;
-type DetachedDepTree = BTreeMap<NodeIndex, BTreeSet<NodeIndex>>;
+pub(crate) type DetachedDepTree = BTreeMap<NodeIndex, BTreeSet<NodeIndex>>;
 
-fn show_detached_dep_tree(tree: &DetachedDepTree, fg: &FeatGraph) -> &'static str {
+fn show_detached_dep_tree(tree: &DetachedDepTree, fg: &FeatGraph) -> String {
     let mut t = tree.iter().collect::<Vec<_>>();
 
     t.sort_by(|a, b| fg.features[*a.0].fid().cmp(&fg.features[*b.0].fid()));
 
+    let mut res = String::new();
     for (&package, feats) in t {
-        //tree.iter() {
         let package = fg.features[package];
-        print!("{package}\n\t");
+        res.push_str(&format!("\n{package}\n\t"));
         for feature in feats.iter().copied() {
             let feature = fg.features[feature];
             let fid = feature.fid().unwrap();
             assert_eq!(package.fid().unwrap().pid, fid.pid);
-            print!("{} ", fid.dep);
+            res.push_str(&format!("{} ", fid.dep));
         }
-        println!();
     }
-    ""
+    res
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -117,7 +106,7 @@ pub enum Collect<'a> {
 // 3. starting from a workspace member, no dev
 // 4. starting from a workspace member, dev for that membe only
 
-fn collect_features_from<M>(
+pub(crate) fn collect_features_from<M>(
     dfs: &mut Dfs<NodeIndex, M>,
     fg: &FeatGraph,
     to: &mut DetachedDepTree,
@@ -141,6 +130,36 @@ fn collect_features_from<M>(
     }
 }
 
+/// Same as [`collect_features_from`] but also follows weak dependency features such as
+/// `"rgb?/serde"` once both sides are collected, graph itself stays unchanged
+pub(crate) fn collect_features_with_triggers<M>(
+    dfs: &mut Dfs<NodeIndex, M>,
+    fg: &FeatGraph,
+    to: &mut DetachedDepTree,
+    filter: Collect,
+) where
+    M: VisitMap<NodeIndex>,
+{
+    loop {
+        collect_features_from(dfs, fg, to, filter);
+        let reached = to.values().flatten().copied().collect::<BTreeSet<_>>();
+        let weak_feats = fg
+            .active_triggers(&reached, filter)
+            .into_iter()
+            .filter_map(|(_, weak_feat)| fg.fid_cache.get(&weak_feat).copied())
+            .filter(|ix| !reached.contains(ix))
+            .collect::<Vec<_>>();
+        if weak_feats.is_empty() {
+            break;
+        }
+        debug!(
+            "Weak dependencies enable {} more features",
+            weak_feats.len()
+        );
+        dfs.stack.extend(weak_feats);
+    }
+}
+
 #[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
 pub enum Ty {
     Dev,
@@ -166,6 +185,84 @@ impl std::fmt::Display for Ty {
     }
 }
 
+/// Workspace members along with feature nodes their builds start from, dependencies first
+fn member_roots<'a>(fg: &FeatGraph<'a>) -> Vec<(Pid<'a>, NodeIndex)> {
+    let workspace_only_graph =
+        NodeFiltered::from_fn(&fg.features, |node| fg.features[node].is_workspace());
+
+    // all the "feature" nodes that belong to the workspace
+    let members_dfs_postorder = DfsPostOrder::new(&workspace_only_graph, fg.root)
+        .iter(&workspace_only_graph)
+        .collect::<Vec<_>>();
+
+    // only feature "roots" nodes, deduplicated
+    let mut res = Vec::new();
+    let mut seen = BTreeSet::new();
+    for member in members_dfs_postorder {
+        if let Some(pid) = fg.features[member].pid() {
+            if seen.contains(&pid) {
+                continue;
+            }
+            seen.insert(pid);
+
+            let package = pid.package();
+            let fid = if package.features.contains_key("default") {
+                pid.named("default")
+            } else {
+                pid.base()
+            };
+            if let Some(&ix) = fg.fid_cache.get(&fid) {
+                res.push((pid, ix));
+            } else {
+                warn!("unknown base in workspace: {fid:?}?");
+            }
+        }
+    }
+    res
+}
+
+/// Features used by the whole workspace, only for crates used on the current target
+fn target_feats(fg: &FeatGraph) -> DetachedDepTree {
+    let mut res = BTreeMap::new();
+    collect_features_from(
+        &mut Dfs::new(&fg.features, fg.root),
+        fg,
+        &mut res,
+        Collect::Target,
+    );
+    res
+}
+
+/// Features every workspace member uses when built on its own, before any unification
+///
+/// Same features [`get_changeset`] starts from for each member: normal dependencies and,
+/// unless `no_dev` is set, dev dependencies of the member itself. Weak dependency features are
+/// followed only within the member's own build.
+pub(crate) fn member_feats<'a>(
+    fg: &FeatGraph<'a>,
+    no_dev: bool,
+) -> BTreeMap<Pid<'a>, DetachedDepTree> {
+    let filtered_workspace_feats = target_feats(fg);
+    let mut res = BTreeMap::new();
+    for (member, member_ix) in member_roots(fg) {
+        let mut deps_feats = BTreeMap::new();
+        let filter = if no_dev {
+            Collect::NoDev
+        } else {
+            Collect::MemberDev(member)
+        };
+        collect_features_with_triggers(
+            &mut Dfs::new(&fg.features, member_ix),
+            fg,
+            &mut deps_feats,
+            filter,
+        );
+        deps_feats.retain(|key, _val| filtered_workspace_feats.contains_key(key));
+        res.insert(member, deps_feats);
+    }
+    res
+}
+
 pub fn get_changeset<'a>(fg: &mut FeatGraph<'a>, no_dev: bool) -> anyhow::Result<FeatChanges<'a>> {
     info!("==== Calculating changeset for hack");
 
@@ -190,53 +287,14 @@ pub fn get_changeset<'a>(fg: &mut FeatGraph<'a>, no_dev: bool) -> anyhow::Result
         // never be used. While we have to care about features added at this step - we can skip
         // them for crates that never will be used - such as winapi on linux. second pass does
         // that.
-        let mut filtered_workspace_feats = BTreeMap::new();
-        collect_features_from(
-            &mut Dfs::new(&fg.features, fg.root),
-            fg,
-            &mut filtered_workspace_feats,
-            Collect::Target,
-        );
+        let filtered_workspace_feats = target_feats(fg);
         raw_workspace_feats.retain(|k, _| filtered_workspace_feats.contains_key(k));
 
         info!(
             "Accumulated workspace dependencies{}",
             show_detached_dep_tree(&raw_workspace_feats, fg)
         );
-        let members = {
-            let workspace_only_graph =
-                NodeFiltered::from_fn(&fg.features, |node| fg.features[node].is_workspace());
-
-            // all the "feature" nodes that belong to the workspace
-            let members_dfs_postorder = DfsPostOrder::new(&workspace_only_graph, fg.root)
-                .iter(&workspace_only_graph)
-                .collect::<Vec<_>>();
-
-            // only feature "roots" nodes, deduplicated
-            let mut res = Vec::new();
-            let mut seen = BTreeSet::new();
-            for member in members_dfs_postorder {
-                if let Some(pid) = fg.features[member].pid() {
-                    if seen.contains(&pid) {
-                        continue;
-                    }
-                    seen.insert(pid);
-
-                    let package = pid.package();
-                    let fid = if package.features.contains_key("default") {
-                        pid.named("default")
-                    } else {
-                        pid.base()
-                    };
-                    if let Some(&ix) = fg.fid_cache.get(&fid) {
-                        res.push((pid, ix));
-                    } else {
-                        warn!("unknown base in workspace: {fid:?}?");
-                    }
-                }
-            }
-            res
-        };
+        let members = member_roots(fg);
 
         for (member, member_ix) in members.iter().copied() {
             info!("==== Checking {member:?}");
@@ -340,58 +398,9 @@ pub fn get_changeset<'a>(fg: &mut FeatGraph<'a>, no_dev: bool) -> anyhow::Result
             }
         }
 
-        // to do triggers we traverse from each triggering package, collect all the
-        // package dependencies and locally enabled features then look for
-        // triggers that satisfy the conditions and not enabled yet then add those,
-        // remove them from fg and do one more pass
-        let mut weak_deps = Vec::new();
-        for (pid, triggers) in &mut fg.triggers {
-            let mut local_fids = BTreeSet::new();
-
-            let mut remote_pids = BTreeSet::new();
-            let mut remote_fids = BTreeSet::new();
-
-            let sub = EdgeFiltered::from_fn(&fg.features, |edge| {
-                fg.features[edge.source()]
-                    .fid()
-                    .map_or(false, |fid| fid.pid == *pid)
-            });
-
-            for edge in sub.edge_references() {
-                if let Some(fid) = fg.features[edge.target()].fid() {
-                    if fid.pid == *pid {
-                        local_fids.insert(fid);
-                    } else {
-                        remote_pids.insert(fid.pid);
-                        remote_fids.insert(fid);
-                    }
-                }
-            }
-
-            if pid.package().features.contains_key("default") {
-                local_fids.insert(pid.named("default"));
-            }
-
-            triggers.retain(|trigger| {
-                if local_fids.contains(&trigger.feature) && remote_pids.contains(&trigger.weak_dep)
-                {
-                    if !remote_fids.contains(&trigger.weak_feat) {
-                        weak_deps.push((trigger.package, trigger.weak_feat));
-                    }
-                    false
-                } else {
-                    true
-                }
-            });
-        }
-
-        if weak_deps.is_empty() {
+        if !apply_weak_triggers(fg)? {
             break;
         }
-        debug!("Weak dependencies add {} new links", weak_deps.len());
-        for (a, b) in weak_deps {
-            fg.add_edge(a, b, false, DepKindInfo::NORMAL)?;
-        }
     }
 
     // renames are needed when there's several dependencies from a member with the same name.
@@ -458,3 +467,61 @@ pub fn get_changeset<'a>(fg: &mut FeatGraph<'a>, no_dev: bool) -> anyhow::Result
         })
         .collect::<BTreeMap<_, _>>())
 }
+
+/// Add links for weak (`foo?/bar`) feature triggers whose conditions are satisfied
+///
+/// Triggers that fire are removed from `fg`, returns `true` if any new links were added
+pub fn apply_weak_triggers(fg: &mut FeatGraph) -> anyhow::Result<bool> {
+    // to do triggers we traverse from each triggering package, collect all the
+    // package dependencies and locally enabled features then look for
+    // triggers that satisfy the conditions and not enabled yet then add those,
+    // remove them from fg and do one more pass
+    let mut weak_deps = Vec::new();
+    for (pid, triggers) in &mut fg.triggers {
+        let mut local_fids = BTreeSet::new();
+
+        let mut remote_pids = BTreeSet::new();
+        let mut remote_fids = BTreeSet::new();
+
+        let sub = EdgeFiltered::from_fn(&fg.features, |edge| {
+            fg.features[edge.source()]
+                .fid()
+                .is_some_and(|fid| fid.pid == *pid)
+        });
+
+        for edge in sub.edge_references() {
+            if let Some(fid) = fg.features[edge.target()].fid() {
+                if fid.pid == *pid {
+                    local_fids.insert(fid);
+                } else {
+                    remote_pids.insert(fid.pid);
+                    remote_fids.insert(fid);
+                }
+            }
+        }
+
+        if pid.package().features.contains_key("default") {
+            local_fids.insert(pid.named("default"));
+        }
+
+        triggers.retain(|trigger| {
+            if local_fids.contains(&trigger.feature) && remote_pids.contains(&trigger.weak_dep) {
+                if !remote_fids.contains(&trigger.weak_feat) {
+                    weak_deps.push((trigger.package, trigger.weak_feat));
+                }
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    if weak_deps.is_empty() {
+        return Ok(false);
+    }
+    debug!("Weak dependencies add {} new links", weak_deps.len());
+    for (a, b) in weak_deps {
+        fg.add_edge(a, b, false, DepKindInfo::NORMAL)?;
+    }
+    Ok(true)
+}
@@ -0,0 +1,9 @@
+//! Escaping for text embedded into HTML reports and SVG images
+
+/// Escape text so it can be used inside of an element or a quoted attribute
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
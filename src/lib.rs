@@ -1,10 +1,23 @@
 #![doc = include_str!("../README.md")]
 
+pub mod api;
+pub mod browse;
+pub mod diff;
+pub mod dupes;
 pub mod explain;
 pub mod feat_graph;
 pub mod hack;
+mod html;
 pub mod mergetool;
 pub mod metadata;
 pub mod opts;
+pub mod origins;
+pub mod render;
+pub mod report;
+pub mod simulate;
 pub mod source;
+pub mod svg;
+#[cfg(test)]
+mod test_util;
 pub mod toml;
+pub mod verify;
@@ -1,18 +1,23 @@
 use anyhow::Context;
 use cargo_hackerman::{
-    explain::{explain, tree},
-    feat_graph::{FeatGraph, Feature},
-    hack::hack,
+    api::Hackerman,
+    browse::browse,
+    diff::{self, GraphDiff},
+    dupes::{Duplicate, Duplicates, FeatureDuplicates, PolicyCheck},
+    explain::{DevFilter, ExplainResult, TreeFilter},
+    hack::{Changeset, Ty},
     mergetool,
-    opts::{self, Action},
+    metadata::Reach,
+    opts::{self, Action, Output},
+    origins::Origin,
+    render::{render, Format},
+    report,
+    simulate::{self, Rebuild},
     toml,
 };
-use cargo_metadata::camino::Utf8PathBuf;
-use cargo_platform::Cfg;
-use std::{
-    collections::{BTreeMap, BTreeSet},
-    str::FromStr,
-};
+use cargo_metadata::{camino::Utf8PathBuf, Version};
+use serde_json::json;
+use std::collections::BTreeSet;
 use tracing::Level;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
@@ -30,16 +35,295 @@ fn start_subscriber(level: Level) {
         .init();
 }
 
-fn get_cfgs() -> anyhow::Result<Vec<Cfg>> {
-    let output = std::process::Command::new("rustc")
-        .arg("--print=cfg")
-        .output()
-        .context("rustc failed to run")?;
-    let stdout = String::from_utf8(output.stdout).unwrap();
-    Ok(stdout
-        .lines()
-        .map(Cfg::from_str)
-        .collect::<Result<Vec<_>, _>>()?)
+fn show_changeset(changeset: &Changeset) {
+    if changeset.is_empty() {
+        println!("Features are unified as is");
+        return;
+    }
+    println!("Hackerman would like to set those features for following packets:");
+    for member in &changeset.members {
+        println!("{}", member.manifest_path);
+        for change in &member.changes {
+            let t = match change.ty {
+                Ty::Dev => "dev ",
+                Ty::Norm => "",
+            };
+            println!("\t{} {}: {t}{:?}", change.name, change.source, change.feats);
+        }
+    }
+}
+
+fn show_result(result: &ExplainResult, output: &Output) -> anyhow::Result<()> {
+    let rendered = render(result, output.format);
+
+    if let Some(path) = &output.output {
+        if path.as_os_str() == "-" {
+            print!("{rendered}");
+        } else {
+            std::fs::write(path, rendered)
+                .with_context(|| format!("Couldn't write graph to {}", path.display()))?;
+        }
+        return Ok(());
+    }
+
+    // HACKERMAN_VIEWER is expected to understand dot files, other formats need explicit --viewer
+    let viewer = match output.format {
+        Format::Dot => output
+            .viewer
+            .clone()
+            .or_else(|| std::env::var("HACKERMAN_VIEWER").ok()),
+        Format::Text | Format::Mermaid | Format::PlantUml | Format::Svg => output.viewer.clone(),
+    };
+    match viewer {
+        Some(viewer) => spawn_viewer(&viewer, &rendered, output.format),
+        None if output.format == Format::Dot && cfg!(feature = "spawn_xdot") => {
+            spawn_viewer("xdot", &rendered, output.format).map_err(|err| {
+                anyhow::anyhow!(
+                    "{err}\nYou can install xdot, pick a different viewer with --viewer or \
+                    HACKERMAN_VIEWER or write the graph to stdout with --output=-"
+                )
+            })
+        }
+        None => {
+            print!("{rendered}");
+            Ok(())
+        }
+    }
+}
+
+fn spawn_viewer(viewer: &str, rendered: &str, format: Format) -> anyhow::Result<()> {
+    use std::io::Write;
+    let mut file = tempfile::Builder::new()
+        .suffix(&format!(".{}", format.extension()))
+        .tempfile()?;
+    file.write_all(rendered.as_bytes())?;
+    file.flush()?;
+
+    let mut words = viewer.split_whitespace();
+    let cmd = words
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Viewer command is empty"))?;
+    let status = std::process::Command::new(cmd)
+        .args(words)
+        .arg(file.path())
+        .status()
+        .with_context(|| format!("Couldn't start viewer {viewer:?}"))?;
+    if !status.success() {
+        anyhow::bail!("Viewer {viewer:?} failed with {status}");
+    }
+    Ok(())
+}
+
+fn show_origins(origins: &[Origin]) {
+    if origins.is_empty() {
+        println!("Nothing enables it directly");
+    }
+    for origin in origins {
+        match &origin.feature {
+            Some(feat) => println!(
+                "{} feature {feat:?} via [{}]",
+                origin.package, origin.section
+            ),
+            None => println!("{} via [{}]", origin.package, origin.section),
+        }
+        match origin.lines {
+            Some((start, end)) if start == end => println!("    {}:{start}", origin.manifest),
+            Some((start, end)) => println!("    {}:{start}-{end}", origin.manifest),
+            None => println!("    {}", origin.manifest),
+        }
+        for line in origin.text.lines() {
+            println!("    {line}");
+        }
+    }
+}
+
+fn show_variants(variants: &simulate::Variants) {
+    if variants.is_empty() {
+        println!("All crates are compiled with a single set of features");
+    }
+    for (pid, feats) in variants {
+        let package = pid.package();
+        println!(
+            "{} {}: {} variants",
+            package.name,
+            package.version,
+            feats.len()
+        );
+        for (feats, origins) in feats {
+            let origins = origins
+                .iter()
+                .map(|origin| origin.map_or("workspace", |pid| pid.package().name.as_str()))
+                .collect::<Vec<_>>();
+            println!("\t{feats:?}: {}", origins.join(", "));
+        }
+    }
+}
+
+fn explain_duplicate(dupe: &Duplicate) {
+    for dep in &dupe.dependents {
+        println!(
+            "    {} <- {} {} requires {}",
+            dep.uses, dep.name, dep.version, dep.req
+        );
+    }
+    match &dupe.unifiable {
+        Some(version) => {
+            // versions some dependent actually resolves to and can move away from
+            let olds = dupe
+                .dependents
+                .iter()
+                .filter(|dep| dep.uses != *version && dep.req.matches(version))
+                .map(|dep| &dep.uses)
+                .collect::<BTreeSet<_>>();
+            for old in olds {
+                println!(
+                    "    all requirements accept {version}, try cargo update -p {}@{old} --precise {version}",
+                    dupe.name
+                );
+            }
+        }
+        None => {
+            for dep in dupe.blocking() {
+                println!(
+                    "    {} {} needs an upgrade to use {} {}",
+                    dep.name,
+                    dep.version,
+                    dupe.name,
+                    dupe.versions
+                        .iter()
+                        .max()
+                        .expect("duplicates have versions")
+                );
+            }
+        }
+    }
+}
+
+fn show_feature_dupes(duplicates: &FeatureDuplicates) {
+    if duplicates.is_empty() {
+        println!("All crates are compiled with a single set of features");
+    }
+    for dupe in &duplicates.crates {
+        println!("{} {}:", dupe.name, dupe.version);
+        for variant in &dupe.variants {
+            println!(
+                "    [{}]: {}",
+                variant.features.join(", "),
+                variant.members.join(", ")
+            );
+        }
+    }
+}
+
+fn feature_dupes_json(duplicates: &FeatureDuplicates) -> serde_json::Value {
+    let crates = duplicates
+        .crates
+        .iter()
+        .map(|dupe| {
+            let variants = dupe
+                .variants
+                .iter()
+                .map(|v| json!({ "features": v.features, "members": v.members }))
+                .collect::<Vec<_>>();
+            json!({
+                "name": dupe.name,
+                "version": dupe.version.to_string(),
+                "variants": variants,
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({ "features": crates })
+}
+
+fn dupes_json(duplicates: &Duplicates, check: Option<&PolicyCheck>) -> serde_json::Value {
+    let strings = |items: &[Version]| items.iter().map(ToString::to_string).collect::<Vec<_>>();
+    let crates = duplicates
+        .crates
+        .iter()
+        .map(|dupe| {
+            let dependents = dupe
+                .dependents
+                .iter()
+                .map(|dep| {
+                    json!({
+                        "name": dep.name,
+                        "version": dep.version.to_string(),
+                        "req": dep.req.to_string(),
+                        "uses": dep.uses.to_string(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            json!({
+                "name": dupe.name,
+                "versions": strings(&dupe.versions),
+                "unifiable": dupe.unifiable.as_ref().map(ToString::to_string),
+                "dependents": dependents,
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut res = json!({ "duplicates": crates });
+    if let Some(check) = check {
+        res["violations"] = check
+            .violations
+            .iter()
+            .map(|v| {
+                json!({
+                    "name": v.name,
+                    "versions": strings(&v.versions),
+                    "denied": strings(&v.denied),
+                })
+            })
+            .collect();
+        res["allowed"] = check
+            .allowed
+            .iter()
+            .map(|a| json!({ "name": a.name, "reason": a.reason }))
+            .collect();
+        res["stale"] = check
+            .stale
+            .iter()
+            .map(|s| json!({ "name": s.name, "version": s.version, "reason": s.reason }))
+            .collect();
+    }
+    res
+}
+
+fn show_diff(changes: &GraphDiff) {
+    if changes.is_empty() {
+        println!("Dependencies are the same");
+        return;
+    }
+    let versions = |versions: &[Version]| {
+        versions
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    for (name, version) in &changes.added {
+        println!("+ {name} {version}");
+    }
+    for (name, version) in &changes.removed {
+        println!("- {name} {version}");
+    }
+    for change in &changes.versions {
+        println!(
+            "~ {} {} -> {}",
+            change.name,
+            versions(&change.old),
+            versions(&change.new)
+        );
+    }
+    for change in &changes.features {
+        let feats = change
+            .added
+            .iter()
+            .map(|f| format!("+{f}"))
+            .chain(change.removed.iter().map(|f| format!("-{f}")))
+            .collect::<Vec<_>>()
+            .join(" ");
+        println!("~ {} {}: {feats}", change.name, change.version);
+    }
 }
 
 fn main() -> anyhow::Result<()> {
@@ -51,17 +335,30 @@ fn main() -> anyhow::Result<()> {
             no_dev,
         } => {
             start_subscriber(profile.verbosity);
-            let metadata = profile.exec()?;
-            let platform = target_spec::Platform::current()?;
-            let triplets = vec![platform.triple_str()];
-            let cfgs = get_cfgs()?;
-            hack(dry, lock, no_dev, &metadata, triplets, cfgs)?;
+            if profile.metadata_file.is_some() && !dry {
+                anyhow::bail!("Saved metadata can only be used with hack --dry");
+            }
+            let hackerman = Hackerman::host(profile.exec()?)?.lock(lock).no_dev(no_dev);
+            if dry {
+                let changeset = hackerman.changeset()?;
+                show_changeset(&changeset);
+                if !changeset.is_empty() {
+                    anyhow::bail!("Features are not unified");
+                }
+            } else {
+                hackerman.hack()?;
+            }
             // regenerate Cargo.lock file
-            profile.exec()?;
+            if profile.metadata_file.is_none() {
+                profile.exec()?;
+            }
         }
 
         Action::Restore { profile, single } => {
             start_subscriber(profile.verbosity);
+            if profile.metadata_file.is_some() {
+                anyhow::bail!("restore edits manifests and can't use saved metadata");
+            }
             let mut changed = false;
             if let Some(path) = single {
                 let utf8_path = Utf8PathBuf::try_from(path)?;
@@ -82,6 +379,9 @@ fn main() -> anyhow::Result<()> {
         }
 
         Action::Check { profile, no_dev } => {
+            if profile.metadata_file.is_some() {
+                anyhow::bail!("check verifies manifests on disk and can't use saved metadata");
+            }
             let metadata = profile.exec()?;
             let members = metadata.workspace_members.iter().collect::<BTreeSet<_>>();
             for package in &metadata.packages {
@@ -89,10 +389,35 @@ fn main() -> anyhow::Result<()> {
                     toml::verify_checksum(package.manifest_path.as_std_path())?;
                 }
             }
-            let platform = target_spec::Platform::current()?;
-            let triplets = vec![platform.triple_str()];
-            let cfgs = get_cfgs()?;
-            hack(true, false, no_dev, &metadata, triplets, cfgs)?;
+            let changeset = Hackerman::host(metadata)?.no_dev(no_dev).changeset()?;
+            show_changeset(&changeset);
+            if !changeset.is_empty() {
+                anyhow::bail!("Features are not unified");
+            }
+        }
+
+        Action::VerifyModel { profile } => {
+            start_subscriber(profile.verbosity);
+            if profile.metadata_file.is_some() {
+                anyhow::bail!("verify-model runs cargo tree and can't use saved metadata");
+            }
+            let hackerman = Hackerman::host(profile.exec()?)?;
+            let members = hackerman.verify_model(&profile.manifest_path)?;
+            let mut count = 0;
+            for member in &members {
+                println!("{}", member.manifest_path);
+                for mismatch in &member.mismatches {
+                    println!("\t{mismatch}");
+                }
+                count += member.mismatches.len();
+            }
+            if count > 0 {
+                anyhow::bail!(
+                    "Found {count} disagreement(s) between cargo and hackerman, \
+                    this is a bug in hackerman, please report it"
+                );
+            }
+            println!("Hackerman's feature model agrees with cargo");
         }
 
         Action::MergeDriver {
@@ -108,27 +433,39 @@ fn main() -> anyhow::Result<()> {
             no_transitive_opt,
             package_nodes,
             workspace,
+            depth,
+            prune,
+            externals_only_first_level,
+            dupes_only,
+            proc_macros_only,
+            edge_labels,
+            output,
             krate,
             feature,
             version,
             no_dev,
         } => {
             start_subscriber(profile.verbosity);
-            let metadata = profile.exec()?;
-            let platform = target_spec::Platform::current()?;
-            let triplets = vec![platform.triple_str()];
-            let cfgs = get_cfgs()?;
-            let mut fg = FeatGraph::init(&metadata, triplets, cfgs)?;
-            fg.optimize(no_transitive_opt)?;
-            tree(
-                &mut fg,
-                krate.as_ref(),
-                feature.as_ref(),
-                version.as_ref(),
-                package_nodes,
+            let filter = TreeFilter {
                 workspace,
                 no_dev,
-            )?;
+                depth,
+                prune,
+                externals_only_first_level,
+            };
+            let result = Hackerman::host(profile.exec()?)?
+                .no_transitive_opt(no_transitive_opt)
+                .package_nodes(package_nodes)
+                .dupes_only(dupes_only)
+                .proc_macros_only(proc_macros_only)
+                .edge_labels(edge_labels)
+                .tree(
+                    krate.as_deref(),
+                    feature.as_deref(),
+                    version.as_ref(),
+                    &filter,
+                )?;
+            show_result(&result, &output)?;
         }
 
         Action::Explain {
@@ -138,23 +475,178 @@ fn main() -> anyhow::Result<()> {
             version,
             no_transitive_opt,
             package_nodes,
+            output,
+            paths,
+            limit,
+            origins,
+            dev_filter,
+            dupes_only,
+            proc_macros_only,
+            edge_labels,
         } => {
             start_subscriber(profile.verbosity);
-            let metadata = profile.exec()?;
-            let platform = target_spec::Platform::current()?;
-            let triplets = vec![platform.triple_str()];
-            let cfgs = get_cfgs()?;
-            let mut fg = FeatGraph::init(&metadata, triplets, cfgs)?;
-            fg.optimize(no_transitive_opt)?;
-
-            explain(
-                &mut fg,
-                &krate,
-                feature.as_ref(),
-                version.as_ref(),
-                package_nodes,
-            )?;
+            if paths && origins {
+                anyhow::bail!("--paths can't be combined with --origins");
+            }
+            if paths || origins {
+                let graph_only = [
+                    (no_transitive_opt, "--no-transitive-opt"),
+                    (package_nodes, "--package-nodes"),
+                    (dupes_only, "--dupes-only"),
+                    (proc_macros_only, "--proc-macros-only"),
+                    (edge_labels, "--edge-labels"),
+                    (output.format != Format::Dot, "--format"),
+                    (output.output.is_some(), "--output"),
+                    (output.viewer.is_some(), "--viewer"),
+                ];
+                if let Some((_, flag)) = graph_only.iter().find(|(set, _)| *set) {
+                    let mode = if paths { "--paths" } else { "--origins" };
+                    anyhow::bail!(
+                        "{flag} only applies to graphs and can't be combined with {mode}"
+                    );
+                }
+            }
+            if origins && dev_filter != DevFilter::All {
+                anyhow::bail!("--no-dev and --dev-only can't be combined with --origins");
+            }
+            if limit.is_some() && !paths {
+                anyhow::bail!("--limit can only be used with --paths");
+            }
+            if origins {
+                let hackerman = Hackerman::host(profile.exec()?)?;
+                let origins = hackerman.origins(&krate, feature.as_deref(), version.as_ref())?;
+                show_origins(&origins);
+                return Ok(());
+            }
+            if paths {
+                let hackerman = Hackerman::host(profile.exec()?)?.dev_filter(dev_filter);
+                let mut member = None;
+                for path in hackerman.paths(
+                    &krate,
+                    feature.as_deref(),
+                    version.as_ref(),
+                    limit.unwrap_or(10),
+                )? {
+                    if member.as_ref() != Some(&path.member) {
+                        println!("{}:", path.member);
+                        member = Some(path.member.clone());
+                    }
+                    let reach = match path.reach {
+                        Reach::Normal => "",
+                        Reach::Build => " (build)",
+                        Reach::Dev => " (dev)",
+                    };
+                    println!("    {}{reach}", path.steps.join(" -> "));
+                }
+                return Ok(());
+            }
+            let result = Hackerman::host(profile.exec()?)?
+                .no_transitive_opt(no_transitive_opt)
+                .package_nodes(package_nodes)
+                .dev_filter(dev_filter)
+                .dupes_only(dupes_only)
+                .proc_macros_only(proc_macros_only)
+                .edge_labels(edge_labels)
+                .explain(&krate, feature.as_deref(), version.as_ref())?;
+            show_result(&result, &output)?;
+        }
+        Action::Simulate {
+            profile,
+            invocation,
+            compare,
+        } => {
+            start_subscriber(profile.verbosity);
+            let hackerman = Hackerman::host(profile.exec()?)?;
+            let mut fg = hackerman.feat_graph()?;
+            let build = simulate::simulate(&mut fg, &invocation)?;
+
+            match compare {
+                None => {
+                    for (pid, feats) in &build.feats {
+                        let package = pid.package();
+                        println!("{} {}: {feats:?}", package.name, package.version);
+                    }
+                }
+                Some(other) => {
+                    let other = simulate::simulate(&mut fg, &other)?;
+                    let rebuilds = simulate::compare(&build, &other);
+                    if rebuilds.is_empty() {
+                        println!("Both invocations compile shared crates with the same features");
+                    }
+                    for (pid, reason) in &rebuilds {
+                        let package = pid.package();
+                        print!("{} {}: ", package.name, package.version);
+                        match reason {
+                            Rebuild::Features => {
+                                println!("{:?} -> {:?}", build.feats[pid], other.feats[pid]);
+                            }
+                            Rebuild::Dependency(dep) => {
+                                let dep = dep.package();
+                                println!("depends on {} {}", dep.name, dep.version);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Action::Variants { profile, no_dev } => {
+            start_subscriber(profile.verbosity);
+            let hackerman = Hackerman::host(profile.exec()?)?.no_dev(no_dev);
+
+            println!("Before hack:");
+            show_variants(&hackerman.variants(false)?);
+            println!("After hack:");
+            show_variants(&hackerman.variants(true)?);
+        }
+
+        Action::Report { profile, html } => {
+            start_subscriber(profile.verbosity);
+            let report = Hackerman::host(profile.exec()?)?.report()?;
+            let rendered = report::html(&report);
+            if html.as_os_str() == "-" {
+                print!("{rendered}");
+            } else {
+                std::fs::write(&html, rendered)
+                    .with_context(|| format!("Couldn't write report to {}", html.display()))?;
+            }
+        }
+
+        Action::Diff {
+            profile,
+            paths,
+            limit,
+            old,
+            new,
+        } => {
+            start_subscriber(profile.verbosity);
+            let old = Hackerman::host(diff::metadata(&profile, &old)?)?;
+            let new = Hackerman::host(match &new {
+                Some(new) => diff::metadata(&profile, new)?,
+                None => profile.exec()?,
+            })?;
+            let changes = new.diff(&old)?;
+            show_diff(&changes);
+            if paths {
+                for (name, version) in &changes.added {
+                    println!("\n{name} {version}:");
+                    for path in new.paths(name, None, Some(version), limit)? {
+                        println!("    {}", path.steps.join(" -> "));
+                    }
+                }
+            }
         }
+
+        Action::Browse {
+            profile,
+            package_nodes,
+        } => {
+            start_subscriber(profile.verbosity);
+            let hackerman = Hackerman::host(profile.exec()?)?;
+            let mut fg = hackerman.feat_graph()?;
+            browse(&mut fg, package_nodes)?;
+        }
+
         Action::ShowCrate {
             profile,
             krate,
@@ -203,49 +695,90 @@ fn main() -> anyhow::Result<()> {
                     } else if cfg!(target_os = "windows") {
                         Command::new("start").arg(url).output()?;
                     } else {
-                        #[cfg(feature = "webbroser")]
-                        {
-                            webbrowser::open(url)?;
-                            return Ok(());
-                        }
+                        #[cfg(feature = "webbrowser")]
+                        webbrowser::open(&url)?;
+                        #[cfg(not(feature = "webbrowser"))]
                         println!("{url}");
                     }
                     return Ok(());
                 }
             }
         }
-        Action::Dupes { profile } => {
-            let mut any = false;
-            let metadata = profile.exec()?;
-            let platform = target_spec::Platform::current()?;
-            let triplets = vec![platform.triple_str()];
-            let cfgs = get_cfgs()?;
-            let mut fg = FeatGraph::init(&metadata, triplets, cfgs)?;
-            fg.shrink_to_target()?;
-
-            let mut packages = BTreeMap::new();
-            for fid in fg.features.node_weights().filter_map(Feature::fid) {
-                if fid == fid.get_base() {
-                    let p = fid.pid.package();
-                    packages
-                        .entry(p.name.clone())
-                        .or_insert_with(Vec::new)
-                        .push(p.clone());
-                }
-            }
-            for (name, copies) in &packages {
-                if copies.len() < 2 {
-                    continue;
-                }
-                any = true;
-                print!("{name}:");
-                for c in copies {
-                    print!(" {}", c.version);
-                }
-                println!();
-            }
-            if !any {
-                println!("All packages are present in one version only");
+        Action::Dupes {
+            profile,
+            explain,
+            deny,
+            json,
+            features,
+        } => {
+            if features && (deny || explain) {
+                anyhow::bail!("--features can't be combined with --deny or --explain");
+            }
+            let hackerman = Hackerman::host(profile.exec()?)?;
+            if features {
+                let duplicates = hackerman.feature_duplicates()?;
+                if json {
+                    println!("{:#}", feature_dupes_json(&duplicates));
+                } else {
+                    show_feature_dupes(&duplicates);
+                }
+                return Ok(());
+            }
+            let duplicates = hackerman.duplicates()?;
+            let check = if deny {
+                Some(hackerman.check_duplicates(&duplicates)?)
+            } else {
+                None
+            };
+            if json {
+                println!("{:#}", dupes_json(&duplicates, check.as_ref()));
+            } else {
+                for dupe in &duplicates.crates {
+                    print!("{}:", dupe.name);
+                    for version in &dupe.versions {
+                        print!(" {version}");
+                    }
+                    let allowed = check
+                        .as_ref()
+                        .and_then(|c| c.allowed.iter().find(|a| a.name == dupe.name));
+                    match allowed.map(|a| &a.reason) {
+                        Some(Some(reason)) => print!(" (allowed: {reason})"),
+                        Some(None) => print!(" (allowed)"),
+                        None => {}
+                    }
+                    println!();
+                    if explain {
+                        explain_duplicate(dupe);
+                    }
+                }
+                if duplicates.is_empty() {
+                    println!("All packages are present in one version only");
+                }
+            }
+            if let Some(check) = check {
+                for stale in &check.stale {
+                    let reason = stale
+                        .reason
+                        .as_ref()
+                        .map_or_else(String::new, |reason| format!(", allowed for: {reason}"));
+                    match &stale.version {
+                        Some(version) => eprintln!(
+                            "warning: allowed version {version} of {} is not used{reason}",
+                            stale.name
+                        ),
+                        None => {
+                            eprintln!("warning: {} is no longer duplicated{reason}", stale.name);
+                        }
+                    }
+                }
+                if !check.is_ok() {
+                    let denied = check
+                        .violations
+                        .iter()
+                        .flat_map(|v| v.denied.iter().map(move |ver| format!("{} {ver}", v.name)))
+                        .collect::<Vec<_>>();
+                    anyhow::bail!("Duplicates not in the allowlist: {}", denied.join(", "));
+                }
             }
         }
     }
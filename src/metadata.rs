@@ -30,17 +30,21 @@ impl From<cargo_metadata::DependencyKind> for DependencyKind {
 pub struct DepKindInfo {
     pub kind: DependencyKind,
     pub target: Option<cargo_platform::Platform>,
+    /// name the dependency is renamed to in the manifest
+    pub rename: Option<String>,
 }
 
 impl DepKindInfo {
     pub const NORMAL: Self = Self {
         kind: DependencyKind::Normal,
         target: None,
+        rename: None,
     };
 
     pub const DEV: Self = Self {
         kind: DependencyKind::Development,
         target: None,
+        rename: None,
     };
 
     fn satisfies(
@@ -81,6 +85,30 @@ impl From<&Dependency> for DepKindInfo {
         Self {
             kind: dep.kind.into(),
             target: dep.target.clone(),
+            rename: dep.rename.clone(),
+        }
+    }
+}
+
+/// How a crate is reachable from a workspace member
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Reach {
+    /// through normal dependencies
+    Normal,
+    /// through build dependencies, possibly along with normal ones
+    Build,
+    /// only through dev dependencies
+    Dev,
+}
+
+impl Reach {
+    pub(crate) fn of(link: &Link) -> Self {
+        if link.kinds.is_empty() || link.kinds.iter().any(|k| k.kind == DependencyKind::Normal) {
+            Reach::Normal
+        } else if link.kinds.iter().any(|k| k.kind == DependencyKind::Build) {
+            Reach::Build
+        } else {
+            Reach::Dev
         }
     }
 }
@@ -90,6 +118,8 @@ pub struct Link {
     /// if dependency is specified as optional or required
     pub optional: bool,
     pub kinds: Vec<DepKindInfo>,
+    /// link is added by a weak dependency feature such as `"rgb?/serde"`
+    pub weak: bool,
 }
 
 impl Link {
@@ -97,12 +127,14 @@ impl Link {
     pub const ALWAYS: Link = Link {
         optional: false,
         kinds: Vec::new(),
+        weak: false,
     };
 
     /// optional lib dependency
     pub const OPT: Link = Link {
         optional: true,
         kinds: Vec::new(),
+        weak: false,
     };
 
     pub(crate) fn is_dev_only(&self) -> bool {
@@ -111,6 +143,54 @@ impl Link {
             .all(|k| k.kind == DependencyKind::Development)
     }
 
+    pub(crate) fn is_build_only(&self) -> bool {
+        !self.kinds.is_empty() && self.kinds.iter().all(|k| k.kind == DependencyKind::Build)
+    }
+
+    /// Dependency kinds, platforms and renames, one entry per distinct declaration
+    ///
+    /// Such as `build cfg(unix) as foo, optional`, empty for plain normal links
+    #[must_use]
+    pub fn label(&self) -> String {
+        let mut parts = self
+            .kinds
+            .iter()
+            .map(|info| {
+                let mut words = Vec::new();
+                match info.kind {
+                    DependencyKind::Normal => {}
+                    DependencyKind::Development => words.push(String::from("dev")),
+                    DependencyKind::Build => words.push(String::from("build")),
+                    DependencyKind::Unknown => words.push(String::from("unknown")),
+                }
+                if let Some(target) = &info.target {
+                    words.push(target.to_string());
+                }
+                if let Some(rename) = &info.rename {
+                    words.push(format!("as {rename}"));
+                }
+                words.join(" ")
+            })
+            .collect::<Vec<_>>();
+        parts.sort();
+        parts.dedup();
+        // plain normal dependency only needs a mention if there's something else
+        match parts.as_slice() {
+            [only] if only.is_empty() => parts.clear(),
+            _ => {
+                for part in &mut parts {
+                    if part.is_empty() {
+                        *part = String::from("normal");
+                    }
+                }
+            }
+        }
+        if self.optional {
+            parts.push(String::from("optional"));
+        }
+        parts.join(", ")
+    }
+
     pub(crate) fn satisfies(
         &self,
         source: Feature,
@@ -123,3 +203,36 @@ impl Link {
             .any(|kind| kind.satisfies(source, filter, platforms, cfgs))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn kind(kind: DependencyKind, target: Option<&str>, rename: Option<&str>) -> DepKindInfo {
+        DepKindInfo {
+            kind,
+            target: target.map(|t| cargo_platform::Platform::from_str(t).unwrap()),
+            rename: rename.map(String::from),
+        }
+    }
+
+    #[test]
+    fn link_labels() {
+        let mut link = Link {
+            optional: false,
+            kinds: vec![DepKindInfo::NORMAL],
+            weak: false,
+        };
+        assert_eq!(link.label(), "");
+        assert!(!link.is_build_only());
+
+        link.kinds = vec![kind(DependencyKind::Build, Some("cfg(unix)"), Some("cc2"))];
+        assert_eq!(link.label(), "build cfg(unix) as cc2");
+        assert!(link.is_build_only());
+
+        link.kinds.push(DepKindInfo::NORMAL);
+        link.optional = true;
+        assert_eq!(link.label(), "normal, build cfg(unix) as cc2, optional");
+    }
+}
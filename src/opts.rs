@@ -1,6 +1,12 @@
-use std::{ffi::OsString, path::PathBuf, str::FromStr};
-
-use bpaf::{positional_if, short, Bpaf, Parser};
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{explain::DevFilter, render::Format};
+use anyhow::Context;
+use bpaf::{long, positional_if, short, Args, Bpaf, Info, ParseFailure, Parser};
 use cargo_metadata::{Metadata, Version};
 use tracing::Level;
 
@@ -39,6 +45,13 @@ pub enum Action {
         no_dev: bool,
     },
 
+    /// Cross-check features predicted by hackerman against cargo
+    #[bpaf(command("verify-model"))]
+    VerifyModel {
+        #[bpaf(external(profile))]
+        profile: Profile,
+    },
+
     /// Restore files and merge with the default merge driver
     #[bpaf(command("merge"))]
     MergeDriver {
@@ -66,6 +79,33 @@ pub enum Action {
         #[bpaf(short('P'), long)]
         package_nodes: bool,
 
+        #[bpaf(external(output))]
+        output: Output,
+
+        /// Print shortest dependency paths from workspace members instead of a graph
+        paths: bool,
+
+        /// Maximum number of paths to print for each workspace member with --paths, defaults to 10
+        #[bpaf(long, argument("N"), from_str(usize), optional)]
+        limit: Option<usize>,
+
+        /// Print manifest declarations enabling the crate or feature instead of a graph,
+        /// can't be used with --paths
+        origins: bool,
+
+        #[bpaf(external(dev_filter))]
+        dev_filter: DevFilter,
+
+        /// Keep only paths leading to crates present in more than one version
+        dupes_only: bool,
+
+        /// Keep only paths going through procedural macros
+        proc_macros_only: bool,
+
+        /// Label links with dependency kinds, platforms and renames
+        edge_labels: bool,
+
+        /// Crate name, glob such as openssl* or /regex/, several can be separated by commas
         #[bpaf(positional("CRATE"))]
         krate: String,
         #[bpaf(external(feature_if))]
@@ -79,6 +119,19 @@ pub enum Action {
     Dupes {
         #[bpaf(external(profile))]
         profile: Profile,
+
+        /// Show who requires each version and if versions can be unified
+        explain: bool,
+
+        /// Fail if duplicates are not allowed by [workspace.metadata.hackerman.dupes]
+        deny: bool,
+
+        /// Print results as JSON
+        json: bool,
+
+        /// List crates compiled with different features when members are built individually,
+        /// can't be used with --deny or --explain
+        features: bool,
     },
 
     #[bpaf(command)]
@@ -103,6 +156,29 @@ pub enum Action {
         #[bpaf(short, long)]
         workspace: bool,
 
+        /// Stop after this many links between packages
+        #[bpaf(long, argument("N"), from_str(usize), optional)]
+        depth: Option<usize>,
+
+        /// Don't follow dependencies of this crate, can be used several times
+        #[bpaf(long, argument("CRATE"), many)]
+        prune: Vec<String>,
+
+        /// Don't follow dependencies of external crates
+        externals_only_first_level: bool,
+
+        /// Keep only paths leading to crates present in more than one version
+        dupes_only: bool,
+
+        /// Keep only paths going through procedural macros
+        proc_macros_only: bool,
+
+        /// Label links with dependency kinds, platforms and renames
+        edge_labels: bool,
+
+        #[bpaf(external(output))]
+        output: Output,
+
         #[bpaf(positional("CRATE"))]
         krate: Option<String>,
         #[bpaf(external(feature_if))]
@@ -111,6 +187,72 @@ pub enum Action {
         version: Option<Version>,
     },
 
+    #[bpaf(command)]
+    /// Compute features each crate is compiled with for a cargo invocation
+    Simulate {
+        #[bpaf(external(profile))]
+        profile: Profile,
+
+        #[bpaf(external(invocation))]
+        invocation: Invocation,
+
+        #[bpaf(external(compare))]
+        compare: Option<Invocation>,
+    },
+
+    /// Lists crates compiled with several different sets of features
+    #[bpaf(command)]
+    Variants {
+        #[bpaf(external(profile))]
+        profile: Profile,
+        /// Don't unify dev dependencies
+        no_dev: bool,
+    },
+
+    #[bpaf(command)]
+    /// Write a self-contained HTML report about workspace dependencies
+    Report {
+        #[bpaf(external(profile))]
+        profile: Profile,
+
+        /// Write the report to this file, use --html=- for stdout
+        #[bpaf(long, argument_os("FILE"))]
+        html: PathBuf,
+    },
+
+    #[bpaf(command)]
+    /// Compare dependencies with a different git revision or saved metadata
+    Diff {
+        #[bpaf(external(profile))]
+        profile: Profile,
+
+        /// Print how newly added crates get into the graph
+        paths: bool,
+
+        /// Print at most this many paths per crate and workspace member
+        #[bpaf(long, argument("N"), from_str(usize), fallback(3))]
+        limit: usize,
+
+        /// Git revision or a file with `cargo metadata` output to compare against
+        #[bpaf(positional("OLD"))]
+        old: String,
+
+        /// Git revision or metadata file to compare, defaults to the working copy
+        #[bpaf(positional("NEW"))]
+        new: Option<String>,
+    },
+
+    #[bpaf(command)]
+    /// Explore the feature graph interactively
+    Browse {
+        #[bpaf(external(profile))]
+        profile: Profile,
+
+        /// Use package nodes instead of feature nodes
+        #[bpaf(short('P'), long)]
+        package_nodes: bool,
+    },
+
     #[bpaf(command("show"))]
     /// Show info about a crate
     ShowCrate {
@@ -125,6 +267,17 @@ pub enum Action {
     },
 }
 
+fn dev_filter() -> Parser<DevFilter> {
+    let no_dev = short('D')
+        .long("no-dev")
+        .help("Don't include dev dependencies")
+        .req_flag(DevFilter::NoDev);
+    let dev_only = long("dev-only")
+        .help("Only show reasons involving dev dependencies")
+        .req_flag(DevFilter::DevOnly);
+    no_dev.or_else(dev_only).fallback(DevFilter::All)
+}
+
 fn feature_if() -> Parser<Option<String>> {
     positional_if("FEATURE", |v| !is_version(v))
 }
@@ -133,12 +286,65 @@ fn version_if() -> Parser<Option<Version>> {
     positional_if("VERSION", is_version).map(|s| s.map(|v| Version::from_str(&v).unwrap()))
 }
 
+#[derive(Debug, Clone, Bpaf)]
+/// Packages and features passed to a cargo command such as `cargo check`
+pub struct Invocation {
+    /// Package to build, defaults to the whole workspace, can be used several times
+    #[bpaf(short('p'), long("package"), argument("SPEC"))]
+    pub packages: Vec<String>,
+
+    /// Space or comma separated list of features to activate, can be used several times
+    #[bpaf(short('F'), long("features"), argument("FEATURES"))]
+    pub features: Vec<String>,
+
+    /// Build all targets, including tests, benches and examples
+    pub all_targets: bool,
+}
+
+fn compare() -> Parser<Option<Invocation>> {
+    long("compare")
+        .help(
+            "Compare against a different invocation, such as --compare=\"-p alpha --all-targets\"",
+        )
+        .argument("ARGS")
+        .parse(|args| {
+            let words = args.split_whitespace().collect::<Vec<_>>();
+            Info::default()
+                .for_parser(invocation())
+                .run_inner(Args::from(words.as_slice()))
+                .map_err(|err| match err {
+                    ParseFailure::Stdout(msg) | ParseFailure::Stderr(msg) => msg,
+                })
+        })
+        .optional()
+}
+
+#[derive(Debug, Clone, Bpaf)]
+/// Where and how to show a dependency graph
+pub struct Output {
+    /// Output format: dot, text, mermaid, plantuml or svg, defaults to dot
+    #[bpaf(long, argument("FORMAT"), from_str(Format), fallback(Format::Dot))]
+    pub format: Format,
+
+    /// Write the graph to a file instead of opening a viewer, use --output=- for stdout
+    #[bpaf(long, argument_os("FILE"))]
+    pub output: Option<PathBuf>,
+
+    /// Open the graph with this command, defaults to $HACKERMAN_VIEWER or xdot
+    #[bpaf(long, argument("CMD"))]
+    pub viewer: Option<String>,
+}
+
 #[derive(Debug, Clone, Bpaf)]
 pub struct Profile {
     #[bpaf(argument_os("PATH"), fallback(profile_fallback()))]
     /// Path to Cargo.toml file, defaults to one in the current directory
     pub manifest_path: PathBuf,
 
+    /// Use saved `cargo metadata` output instead of running cargo, use - for stdin
+    #[bpaf(argument_os("FILE"))]
+    pub metadata_file: Option<PathBuf>,
+
     /// Require Cargo.lock and cache are up to date
     pub frozen: bool,
     /// Require Cargo.lock is up to date
@@ -152,6 +358,14 @@ pub struct Profile {
 
 impl Profile {
     pub fn exec(&self) -> anyhow::Result<Metadata> {
+        match &self.metadata_file {
+            Some(path) => read_metadata(path),
+            None => self.exec_at(&self.manifest_path),
+        }
+    }
+
+    /// Same flags, different manifest, such as one in a temporary checkout
+    pub fn exec_at(&self, manifest_path: &Path) -> anyhow::Result<Metadata> {
         let mut cmd = cargo_metadata::MetadataCommand::new();
 
         let mut extra = Vec::new();
@@ -164,13 +378,24 @@ impl Profile {
         if self.offline {
             extra.push(String::from("--offline"));
         }
-        cmd.manifest_path(&self.manifest_path);
+        cmd.manifest_path(manifest_path);
         cmd.other_options(extra);
 
         Ok(cmd.exec()?)
     }
 }
 
+/// Parse saved `cargo metadata` output, `-` reads it from stdin
+pub fn read_metadata(path: &Path) -> anyhow::Result<Metadata> {
+    let data = if path.as_os_str() == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read metadata from {}", path.display()))?
+    };
+    Ok(cargo_metadata::MetadataCommand::parse(data)?)
+}
+
 fn profile_fallback() -> PathBuf {
     "Cargo.toml".into()
 }
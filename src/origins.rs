@@ -0,0 +1,319 @@
+use crate::{
+    explain::collect_packages,
+    feat_graph::{Feat, FeatGraph, Fid},
+    hack::Collect,
+};
+use cargo_metadata::{camino::Utf8PathBuf, DependencyKind, Package, Version};
+use petgraph::visit::EdgeRef;
+use tracing::{debug, info};
+
+/// Place in a manifest responsible for enabling a feature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    /// package that enables the feature, name and version
+    pub package: String,
+    /// feature of the package that enables the feature, `None` for the package itself
+    pub feature: Option<String>,
+    /// manifest, `Cargo.toml.orig` for packages from registries
+    pub manifest: Utf8PathBuf,
+    /// table containing the declaration, such as `features` or `dev-dependencies`
+    pub section: String,
+    /// first and last lines of the declaration, 1 based, `None` if it couldn't be found or the
+    /// manifest couldn't be read
+    pub lines: Option<(usize, usize)>,
+    /// declaration text
+    pub text: String,
+}
+
+/// Map every link to a crate/feature back to the manifest declarations responsible for it
+///
+/// Returns `None` if there's no such crate.
+pub fn origins(
+    fg: &mut FeatGraph,
+    krate: &str,
+    feature: Option<&str>,
+    version: Option<&Version>,
+) -> anyhow::Result<Option<Vec<Origin>>> {
+    fg.shrink_to_target(Collect::DevTarget)?;
+    let targets = collect_packages(fg, krate, feature, version)?;
+    info!("Found {} matching package(s)", targets.len());
+    if targets.is_empty() {
+        return Ok(None);
+    }
+
+    let mut res = Vec::new();
+    for ix in targets {
+        let target = match fg.features[ix].fid() {
+            Some(fid) => fid,
+            None => continue,
+        };
+        let mut sources = fg
+            .features
+            .edges_directed(ix, petgraph::EdgeDirection::Incoming)
+            .filter_map(|edge| fg.features[edge.source()].fid())
+            .collect::<Vec<_>>();
+        sources.sort();
+        sources.dedup();
+        for source in sources {
+            // every feature implies its own package, there's nothing to point at
+            if source.pid == target.pid && target.dep == Feat::Base {
+                continue;
+            }
+            for origin in edge_origins(source, target) {
+                if !res.contains(&origin) {
+                    res.push(origin);
+                }
+            }
+        }
+    }
+    debug!("Found {} origin(s)", res.len());
+    Ok(Some(res))
+}
+
+fn edge_origins(source: Fid, target: Fid) -> Vec<Origin> {
+    let package = source.pid.package();
+    let manifest = manifest_path(package);
+    // metadata can be saved on a different machine, origins are still useful without lines
+    let text = match std::fs::read_to_string(&manifest) {
+        Ok(text) => Some(text),
+        Err(err) => {
+            debug!("Couldn't read {manifest}: {err}");
+            None
+        }
+    };
+    let name = format!("{} {}", package.name, package.version);
+
+    // cargo lists implicit features of optional dependencies along with declared ones
+    let declared = |feat: &str| match &text {
+        Some(text) => locate(text, "features", feat).is_some(),
+        None => package
+            .features
+            .get(feat)
+            .is_some_and(|deps| *deps != [format!("dep:{feat}")]),
+    };
+
+    let mut locations = Vec::new();
+    match source.dep {
+        Feat::Named(feat) if declared(feat) => {
+            locations.push((String::from("features"), feat.to_string()));
+        }
+        feat => {
+            let target_package = target.pid.package();
+            let deps = package
+                .dependencies
+                .iter()
+                .filter(|dep| {
+                    dep.name == target_package.name && dep.req.matches(&target_package.version)
+                })
+                .filter(|dep| match feat {
+                    // optional dependencies are enabled by implicit features named after them
+                    Feat::Named(name) => {
+                        dep.optional && dep.rename.as_ref().unwrap_or(&dep.name) == name
+                    }
+                    Feat::Base => !dep.optional,
+                })
+                .collect::<Vec<_>>();
+            let enabling = deps
+                .iter()
+                .filter(|dep| match target.dep {
+                    Feat::Base => true,
+                    Feat::Named("default") => dep.uses_default_features,
+                    Feat::Named(name) => dep.features.iter().any(|f| f == name),
+                })
+                .collect::<Vec<_>>();
+            let deps = if enabling.is_empty() {
+                deps.iter().collect()
+            } else {
+                enabling
+            };
+            for dep in deps {
+                let table = match dep.kind {
+                    DependencyKind::Development => "dev-dependencies",
+                    DependencyKind::Build => "build-dependencies",
+                    _ => "dependencies",
+                };
+                let section = match &dep.target {
+                    Some(platform) => format!("target.{platform}.{table}"),
+                    None => table.to_string(),
+                };
+                let key = dep.rename.as_ref().unwrap_or(&dep.name).clone();
+                locations.push((section, key));
+            }
+        }
+    }
+
+    locations
+        .into_iter()
+        .map(|(section, key)| {
+            let lines = text
+                .as_deref()
+                .and_then(|text| Some((text, locate(text, &section, &key)?)));
+            let snippet = match lines {
+                Some((text, (start, end))) => text
+                    .lines()
+                    .skip(start - 1)
+                    .take(end - start + 1)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                None => format!("{key} = ..."),
+            };
+            Origin {
+                package: name.clone(),
+                feature: match source.dep {
+                    Feat::Base => None,
+                    Feat::Named(name) => Some(name.to_string()),
+                },
+                manifest: manifest.clone(),
+                section,
+                lines: lines.map(|(_, lines)| lines),
+                text: snippet,
+            }
+        })
+        .collect()
+}
+
+/// Original manifest as written by the crate author, cargo normalizes the packaged one
+pub(crate) fn manifest_path(package: &Package) -> Utf8PathBuf {
+    let orig = package.manifest_path.with_extension("toml.orig");
+    if orig.exists() {
+        orig
+    } else {
+        package.manifest_path.clone()
+    }
+}
+
+/// Table name or key with quotes and whitespace removed
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| !matches!(c, '"' | '\'') && !c.is_whitespace())
+        .collect()
+}
+
+fn bracket_balance(line: &str) -> i32 {
+    let mut balance = 0;
+    let mut quote = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '#') => break,
+            (None, '[' | '{') => balance += 1,
+            (None, ']' | '}') => balance -= 1,
+            _ => {}
+        }
+    }
+    balance
+}
+
+/// Find lines declaring `key` in `section`, either as `key = ...` or as a `[section.key]` table
+fn locate(manifest: &str, section: &str, key: &str) -> Option<(usize, usize)> {
+    let section = normalize(section);
+    let key = normalize(key);
+    let table = format!("{section}.{key}");
+
+    let lines = manifest.lines().collect::<Vec<_>>();
+    let mut current = String::new();
+    for (ix, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            current = normalize(trimmed.trim_start_matches('[').split(']').next()?);
+            if current == table {
+                let mut end = ix;
+                for (next, line) in lines.iter().enumerate().skip(ix + 1) {
+                    let line = line.trim();
+                    if line.starts_with('[') {
+                        break;
+                    }
+                    if !line.is_empty() && !line.starts_with('#') {
+                        end = next;
+                    }
+                }
+                return Some((ix + 1, end + 1));
+            }
+            continue;
+        }
+        if current != section {
+            continue;
+        }
+        let this_key = match trimmed.split_once('=') {
+            Some((this_key, _)) => normalize(this_key),
+            None => continue,
+        };
+        if this_key == key {
+            let mut balance = bracket_balance(line);
+            let mut end = ix;
+            while balance > 0 && end + 1 < lines.len() {
+                end += 1;
+                balance += bracket_balance(lines[end]);
+            }
+            return Some((ix + 1, end + 1));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::process_fg_with;
+
+    const MANIFEST: &str = r#"[package]
+name = "alpha"
+
+[dependencies]
+serde = { version = "1", features = ["derive"] }
+"reqwest" = { version = "0.11", features = [
+    "rustls-tls",
+] }
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+
+[dev-dependencies.tokio]
+version = "1"
+features = ["full"]
+
+[features]
+default = ["std"]
+std = ["serde/std"] # comment [
+"#;
+
+    #[test]
+    fn locate_declarations() {
+        assert_eq!(locate(MANIFEST, "dependencies", "serde"), Some((5, 5)));
+        assert_eq!(locate(MANIFEST, "dependencies", "reqwest"), Some((6, 8)));
+        assert_eq!(
+            locate(MANIFEST, "target.cfg(unix).dependencies", "libc"),
+            Some((11, 11))
+        );
+        assert_eq!(
+            locate(MANIFEST, "dev-dependencies", "tokio"),
+            Some((13, 15))
+        );
+        assert_eq!(locate(MANIFEST, "features", "std"), Some((19, 19)));
+        assert_eq!(locate(MANIFEST, "features", "full"), None);
+    }
+
+    #[test]
+    fn missing_manifest() -> anyhow::Result<()> {
+        // manifests in saved metadata point to a different machine
+        process_fg_with(5, |fg| {
+            let found = origins(fg, "gamma", Some("one"), None)?;
+            let found = found
+                .unwrap_or_default()
+                .into_iter()
+                .map(|origin| (origin.package, origin.feature, origin.section, origin.lines))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                found,
+                [(
+                    "alpha 0.1.0".to_string(),
+                    Some("one".to_string()),
+                    "features".to_string(),
+                    None
+                )]
+            );
+            Ok(())
+        })
+    }
+}
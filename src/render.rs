@@ -0,0 +1,498 @@
+use crate::{
+    explain::{Edge, ExplainResult, Node},
+    feat_graph::package_markers,
+    metadata::Reach,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    str::FromStr,
+};
+
+/// Output format for `explain` and `tree` commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// graphviz dot
+    Dot,
+    /// indented plain text, similar to `cargo tree`
+    Text,
+    /// mermaid flowchart, rendered by GitHub and GitLab markdown
+    Mermaid,
+    /// PlantUML diagram
+    PlantUml,
+    /// standalone SVG image, doesn't require graphviz
+    Svg,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(Format::Dot),
+            "text" => Ok(Format::Text),
+            "mermaid" => Ok(Format::Mermaid),
+            "plantuml" => Ok(Format::PlantUml),
+            "svg" => Ok(Format::Svg),
+            _ => Err(format!(
+                "Unknown format {s}, expected one of: dot, text, mermaid, plantuml, svg"
+            )),
+        }
+    }
+}
+
+impl Format {
+    /// File extension for the rendered output
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Dot => "dot",
+            Format::Text => "txt",
+            Format::Mermaid => "mmd",
+            Format::PlantUml => "puml",
+            Format::Svg => "svg",
+        }
+    }
+}
+
+/// Render the result in a given format
+#[must_use]
+pub fn render(result: &ExplainResult, format: Format) -> String {
+    match format {
+        Format::Dot => result.dot.clone(),
+        Format::Text => text(result),
+        Format::Mermaid => mermaid(result),
+        Format::PlantUml => plantuml(result),
+        Format::Svg => crate::svg::svg(result),
+    }
+}
+
+/// Render the result as an indented tree, similar to `cargo tree`
+///
+/// Results of `explain` start from the explained crate and go towards the workspace members,
+/// results of `tree` start from the workspace members. Subtrees already shown are marked
+/// with `(*)`.
+#[must_use]
+pub fn text(result: &ExplainResult) -> String {
+    let nodes = result
+        .nodes
+        .iter()
+        .map(|node| (node.id, node))
+        .collect::<BTreeMap<_, _>>();
+
+    let inverted = result.nodes.iter().any(|node| node.target);
+    let mut children = BTreeMap::<usize, Vec<&Edge>>::new();
+    let mut has_parent = BTreeSet::new();
+    for edge in &result.edges {
+        let (parent, child) = if inverted {
+            (edge.to, edge.from)
+        } else {
+            (edge.from, edge.to)
+        };
+        children.entry(parent).or_default().push(edge);
+        has_parent.insert(child);
+    }
+    let child_of = |edge: &Edge| if inverted { edge.from } else { edge.to };
+    for edges in children.values_mut() {
+        edges.sort_by_key(|edge| sort_key(nodes[&child_of(edge)]));
+    }
+
+    let mut roots = if inverted {
+        result
+            .nodes
+            .iter()
+            .filter(|node| node.target)
+            .collect::<Vec<_>>()
+    } else {
+        result
+            .nodes
+            .iter()
+            .filter(|node| !has_parent.contains(&node.id))
+            .collect::<Vec<_>>()
+    };
+    // everything is a part of some cycle
+    if roots.is_empty() {
+        roots.extend(result.nodes.first());
+    }
+    roots.sort_by_key(|node| sort_key(node));
+
+    let mut out = String::new();
+    let mut seen = BTreeSet::new();
+    let mut stack = Vec::new();
+    for root in roots {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        stack.push((root.id, None, String::new(), String::new()));
+        while let Some((id, edge, prefix, child_prefix)) = stack.pop() {
+            let node = nodes[&id];
+            out.push_str(&prefix);
+            out.push_str(&node_text(node));
+            if let Some(edge) = edge {
+                out.push_str(&edge_text(edge));
+            }
+            let expanded = seen.insert(id);
+            let edges = children.get(&id).map_or(&[][..], Vec::as_slice);
+            if !expanded && !edges.is_empty() {
+                out.push_str(" (*)");
+            }
+            out.push('\n');
+            if !expanded {
+                continue;
+            }
+            for (ix, edge) in edges.iter().enumerate().rev() {
+                let last = ix + 1 == edges.len();
+                let (this, next) = if last {
+                    ("└── ", "    ")
+                } else {
+                    ("├── ", "│   ")
+                };
+                stack.push((
+                    child_of(edge),
+                    Some(*edge),
+                    format!("{child_prefix}{this}"),
+                    format!("{child_prefix}{next}"),
+                ));
+            }
+        }
+    }
+    out
+}
+
+fn sort_key(node: &Node) -> (&str, Option<&str>) {
+    (&node.label, node.feature.as_deref())
+}
+
+fn node_text(node: &Node) -> String {
+    let mut res = node.label.clone();
+    if let Some(feature) = &node.feature {
+        let _ = write!(res, " feature \"{feature}\"");
+    }
+    if let Some(reach) = reach_text(node) {
+        let _ = write!(res, " ({reach})");
+    }
+    if let Some(markers) = markers_text(node) {
+        let _ = write!(res, " ({markers})");
+    }
+    res
+}
+
+/// How the explained crate is reachable from a workspace member, if it's not obvious
+pub(crate) fn reach_text(node: &Node) -> Option<&'static str> {
+    match node.reach? {
+        Reach::Normal => None,
+        Reach::Build => Some("build only"),
+        Reach::Dev => Some("dev only"),
+    }
+}
+
+/// Proc macro, build script and native library markers, only base crates get them
+pub(crate) fn markers_text(node: &Node) -> Option<String> {
+    if node.feature.is_some() {
+        return None;
+    }
+    package_markers(node.proc_macro, node.build_script, node.links.as_deref())
+}
+
+fn edge_text(edge: &Edge) -> String {
+    let mut res = String::new();
+    // labels already mention dependency kinds and optional links
+    if let Some(label) = &edge.label {
+        let _ = write!(res, " [{label}]");
+    } else if edge.dev_only {
+        res.push_str(" (dev)");
+    } else if edge.build_only {
+        res.push_str(" (build)");
+    }
+    if edge.weak {
+        res.push_str(" (weak)");
+    } else if edge.optional && edge.label.is_none() {
+        res.push_str(" (optional)");
+    }
+    res
+}
+
+/// Multi line label: package name and version followed by a feature name or crate markers
+pub(crate) fn label_lines(node: &Node) -> Vec<String> {
+    let mut lines = vec![node.label.clone()];
+    lines.extend(node.feature.clone());
+    lines.extend(markers_text(node));
+    lines.extend(reach_text(node).map(String::from));
+    lines
+}
+
+/// Render the result as a mermaid flowchart
+///
+/// Base crates are drawn as hexagons, features as rounded boxes, external crates are filled,
+/// crates present in several versions are gold, proc macros are subroutine boxes and crates
+/// linking to native libraries are blue. Dev-only links are dashed, build-only links are
+/// thick, weak links are dotted and optional links are grey.
+#[must_use]
+pub fn mermaid(result: &ExplainResult) -> String {
+    let mut out = String::from("flowchart TD\n");
+    let mut external = Vec::new();
+    for node in &result.nodes {
+        let label = label_lines(node).join("<br>").replace('"', "#quot;");
+        let _ = match node.feature {
+            None if node.proc_macro => writeln!(out, "    n{}[[\"{label}\"]]", node.id),
+            None => writeln!(out, "    n{}{{{{\"{label}\"}}}}", node.id),
+            Some(_) => writeln!(out, "    n{}(\"{label}\")", node.id),
+        };
+        if !node.workspace {
+            external.push(format!("n{}", node.id));
+        }
+    }
+
+    let mut styles = BTreeMap::<String, Vec<String>>::new();
+    for (ix, edge) in result.edges.iter().enumerate() {
+        let _ = match &edge.label {
+            Some(label) => writeln!(
+                out,
+                "    n{} -->|\"{}\"| n{}",
+                edge.from,
+                label.replace('"', "#quot;"),
+                edge.to
+            ),
+            None => writeln!(out, "    n{} --> n{}", edge.from, edge.to),
+        };
+        let mut style = Vec::new();
+        if edge.weak {
+            style.push("stroke-dasharray:2 2");
+        } else if edge.dev_only {
+            style.push("stroke-dasharray:5 5");
+        } else if edge.build_only {
+            style.push("stroke-width:3px");
+        }
+        if edge.optional {
+            style.push("stroke:grey");
+        }
+        if !style.is_empty() {
+            styles
+                .entry(style.join(","))
+                .or_default()
+                .push(ix.to_string());
+        }
+    }
+    for (style, edges) in styles {
+        let _ = writeln!(out, "    linkStyle {} {style}", edges.join(","));
+    }
+
+    if !external.is_empty() {
+        out.push_str("    classDef external fill:lightgrey\n");
+        let _ = writeln!(out, "    class {} external", external.join(","));
+    }
+    for node in &result.nodes {
+        if node.target {
+            let _ = writeln!(out, "    style n{} fill:pink", node.id);
+        } else if node.cycle {
+            let _ = writeln!(out, "    style n{} fill:orange", node.id);
+        } else if node.duplicate {
+            let _ = writeln!(out, "    style n{} fill:gold", node.id);
+        } else if node.links.is_some() {
+            let _ = writeln!(out, "    style n{} fill:lightblue", node.id);
+        }
+    }
+    out
+}
+
+/// Render the result as a PlantUML diagram
+///
+/// Uses the same conventions as `mermaid`: base crates are hexagons, features are ovals,
+/// external crates are filled, duplicated crates are gold, proc macros are components, crates
+/// linking to native libraries are blue, dev-only links are dashed, build-only links are thick,
+/// weak links are dotted and optional links are grey.
+#[must_use]
+pub fn plantuml(result: &ExplainResult) -> String {
+    let mut out = String::from("@startuml\n");
+    for node in &result.nodes {
+        let shape = match node.feature {
+            None if node.proc_macro => "component",
+            None => "hexagon",
+            Some(_) => "usecase",
+        };
+        let label = label_lines(node).join("\\n").replace('"', "'");
+        let color = if node.target {
+            " #pink"
+        } else if node.cycle {
+            " #orange"
+        } else if node.duplicate {
+            " #gold"
+        } else if node.links.is_some() {
+            " #lightblue"
+        } else if !node.workspace {
+            " #lightgrey"
+        } else {
+            ""
+        };
+        let _ = writeln!(out, "{shape} \"{label}\" as n{}{color}", node.id);
+    }
+    for edge in &result.edges {
+        let mut style = Vec::new();
+        if edge.optional {
+            style.push("#grey");
+        }
+        if edge.weak {
+            style.push("dotted");
+        } else if edge.dev_only {
+            style.push("dashed");
+        } else if edge.build_only {
+            style.push("bold");
+        }
+        let arrow = if style.is_empty() {
+            String::from("-->")
+        } else {
+            format!("-[{}]->", style.join(","))
+        };
+        let _ = match &edge.label {
+            Some(label) => writeln!(out, "n{} {arrow} n{} : {label}", edge.from, edge.to),
+            None => writeln!(out, "n{} {arrow} n{}", edge.from, edge.to),
+        };
+    }
+    out.push_str("@enduml\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: usize, label: &str, feature: Option<&str>, target: bool) -> Node {
+        Node {
+            id,
+            package: None,
+            feature: feature.map(String::from),
+            label: label.to_string(),
+            workspace: false,
+            target,
+            cycle: false,
+            duplicate: false,
+            proc_macro: false,
+            build_script: false,
+            links: None,
+            reach: None,
+        }
+    }
+
+    fn edge(from: usize, to: usize, dev_only: bool) -> Edge {
+        Edge {
+            from,
+            to,
+            optional: false,
+            dev_only,
+            build_only: false,
+            weak: false,
+            label: None,
+        }
+    }
+
+    #[test]
+    fn text_tree_marks_repeated_subtrees() {
+        let result = ExplainResult {
+            nodes: vec![
+                node(0, "alpha", None, false),
+                node(1, "beta", None, false),
+                node(2, "gamma", Some("one"), false),
+                node(3, "gamma", None, false),
+            ],
+            edges: vec![
+                edge(0, 1, false),
+                edge(0, 2, true),
+                edge(1, 2, false),
+                edge(2, 3, false),
+            ],
+            dot: String::new(),
+        };
+        let expected = "\
+alpha
+├── beta
+│   └── gamma feature \"one\"
+│       └── gamma
+└── gamma feature \"one\" (dev) (*)
+";
+        assert_eq!(text(&result), expected);
+    }
+
+    #[test]
+    fn text_explain_starts_from_target() {
+        let result = ExplainResult {
+            nodes: vec![node(0, "alpha", None, false), node(1, "beta", None, true)],
+            edges: vec![edge(0, 1, false)],
+            dot: String::new(),
+        };
+        assert_eq!(text(&result), "beta\n└── alpha\n");
+    }
+
+    fn demo_result() -> ExplainResult {
+        let mut member = node(0, "alpha", None, false);
+        member.workspace = true;
+        let mut optional = edge(0, 2, false);
+        optional.optional = true;
+        ExplainResult {
+            nodes: vec![
+                member,
+                node(1, "beta 0.1.0", None, true),
+                node(2, "gamma 0.2.0", Some("one"), false),
+            ],
+            edges: vec![edge(0, 1, true), optional],
+            dot: String::new(),
+        }
+    }
+
+    #[test]
+    fn mermaid_flowchart() {
+        let expected = r#"flowchart TD
+    n0{{"alpha"}}
+    n1{{"beta 0.1.0"}}
+    n2("gamma 0.2.0<br>one")
+    n0 --> n1
+    n0 --> n2
+    linkStyle 0 stroke-dasharray:5 5
+    linkStyle 1 stroke:grey
+    classDef external fill:lightgrey
+    class n1,n2 external
+    style n1 fill:pink
+"#;
+        assert_eq!(mermaid(&demo_result()), expected);
+    }
+
+    #[test]
+    fn plantuml_diagram() {
+        let expected = r#"@startuml
+hexagon "alpha" as n0
+hexagon "beta 0.1.0" as n1 #pink
+usecase "gamma 0.2.0\none" as n2 #lightgrey
+n0 -[dashed]-> n1
+n0 -[#grey]-> n2
+@enduml
+"#;
+        assert_eq!(plantuml(&demo_result()), expected);
+    }
+
+    #[test]
+    fn crate_markers() {
+        let mut openssl = node(0, "openssl-sys 0.9.72", None, false);
+        openssl.build_script = true;
+        openssl.links = Some(String::from("openssl"));
+        let mut derive = node(1, "serde_derive 1.0.137", None, false);
+        derive.proc_macro = true;
+        let mut feature = node(2, "serde_derive 1.0.137", Some("default"), false);
+        feature.proc_macro = true;
+
+        assert_eq!(
+            node_text(&openssl),
+            "openssl-sys 0.9.72 (build.rs, links = openssl)"
+        );
+        assert_eq!(
+            node_text(&feature),
+            "serde_derive 1.0.137 feature \"default\""
+        );
+        let result = ExplainResult {
+            nodes: vec![openssl, derive, feature],
+            edges: Vec::new(),
+            dot: String::new(),
+        };
+        let chart = mermaid(&result);
+        assert!(chart.contains("    n1[[\"serde_derive 1.0.137<br>proc-macro\"]]\n"));
+        assert!(chart.contains("    style n0 fill:lightblue\n"));
+    }
+}
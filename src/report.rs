@@ -0,0 +1,306 @@
+//! Self-contained HTML report about the workspace dependencies
+//!
+//! Report is a single file with no scripts or external assets so it can be attached to an
+//! issue or sent over email. Trees are collapsible with plain `<details>` elements.
+
+use crate::{
+    dupes::Duplicates,
+    feat_graph::{Feat, FeatGraph, Pid},
+    hack::{Changeset, Ty},
+    html::escape,
+};
+use petgraph::{graph::NodeIndex, visit::EdgeRef};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+/// Everything that goes into the report
+#[derive(Debug)]
+pub struct Report {
+    /// dependency tree for each workspace member
+    pub trees: Vec<TreeNode>,
+    /// crates present in more than one version
+    pub duplicates: Duplicates,
+    /// changes `hack` would make
+    pub changeset: Changeset,
+    /// features each crate is compiled with when the whole workspace is built
+    pub features: Vec<CrateFeatures>,
+}
+
+/// Package level dependency tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeNode {
+    /// package name and version
+    pub label: String,
+    /// package is reachable from the parent by dev dependencies only
+    pub dev_only: bool,
+    /// dependencies are already listed elsewhere in this tree
+    pub repeated: bool,
+    pub children: Vec<TreeNode>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CrateFeatures {
+    /// package name and version
+    pub label: String,
+    /// named features in use, sorted
+    pub features: Vec<String>,
+}
+
+/// Package level dependency tree for every workspace member, sorted by name
+///
+/// Graph should be shrunk to the target first. Each package is expanded at most once per tree,
+/// repeated occurrences are marked instead.
+#[must_use]
+pub fn member_trees(fg: &FeatGraph) -> Vec<TreeNode> {
+    // package labels, not every package has a node for its default feature
+    let labels = fg
+        .features
+        .node_indices()
+        .filter_map(|ix| Some((fg.features[ix].pid()?, ix)))
+        .map(|(pid, ix)| (pid, fg.package_label(ix)))
+        .collect::<BTreeMap<_, _>>();
+    // package dependencies, `true` for dev only links
+    let mut deps = BTreeMap::<Pid, BTreeMap<Pid, bool>>::new();
+    for edge in fg.features.edge_references() {
+        let (a, b) = match (
+            fg.features[edge.source()].pid(),
+            fg.features[edge.target()].pid(),
+        ) {
+            (Some(a), Some(b)) if a != b => (a, b),
+            _ => continue,
+        };
+        let dev_only = deps.entry(a).or_default().entry(b).or_insert(true);
+        *dev_only &= edge.weight().is_dev_only();
+    }
+
+    let mut trees = fg
+        .workspace_members
+        .iter()
+        .map(|&member| {
+            let mut seen = BTreeSet::new();
+            expand(&labels, &deps, member, false, &mut seen)
+        })
+        .collect::<Vec<_>>();
+    trees.sort_by(|a, b| a.label.cmp(&b.label));
+    trees
+}
+
+fn expand<'a>(
+    labels: &BTreeMap<Pid<'a>, String>,
+    deps: &BTreeMap<Pid<'a>, BTreeMap<Pid<'a>, bool>>,
+    pid: Pid<'a>,
+    dev_only: bool,
+    seen: &mut BTreeSet<Pid<'a>>,
+) -> TreeNode {
+    let label = labels.get(&pid).cloned().unwrap_or_default();
+    let children = deps.get(&pid);
+    if !seen.insert(pid) {
+        return TreeNode {
+            label,
+            dev_only,
+            repeated: children.is_some_and(|c| !c.is_empty()),
+            children: Vec::new(),
+        };
+    }
+    let mut children = children
+        .into_iter()
+        .flatten()
+        .map(|(&child, &dev_only)| expand(labels, deps, child, dev_only, seen))
+        .collect::<Vec<_>>();
+    children.sort_by(|a, b| a.label.cmp(&b.label));
+    TreeNode {
+        label,
+        dev_only,
+        repeated: false,
+        children,
+    }
+}
+
+/// Named features of every package present in the graph, sorted by package
+///
+/// Graph should be shrunk to the target first.
+#[must_use]
+pub fn crate_features(fg: &FeatGraph) -> Vec<CrateFeatures> {
+    let mut features = BTreeMap::<Pid, (NodeIndex, Vec<String>)>::new();
+    for ix in fg.features.node_indices() {
+        let fid = match fg.features[ix].fid() {
+            Some(fid) => fid,
+            None => continue,
+        };
+        let entry = features.entry(fid.pid).or_insert_with(|| (ix, Vec::new()));
+        if let Feat::Named(name) = fid.dep {
+            entry.1.push(name.to_string());
+        }
+    }
+    let mut res = features
+        .into_values()
+        .map(|(ix, mut features)| {
+            features.sort();
+            CrateFeatures {
+                label: fg.package_label(ix),
+                features,
+            }
+        })
+        .collect::<Vec<_>>();
+    res.sort_by(|a, b| a.label.cmp(&b.label));
+    res
+}
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+ul { list-style: none; padding-left: 1.5em; margin: 0; }
+summary { cursor: pointer; }
+.dev { color: grey; }
+.note { color: grey; font-size: smaller; }
+table { border-collapse: collapse; }
+td, th { border: 1px solid lightgrey; padding: 0.2em 0.6em; text-align: left; vertical-align: top; }
+";
+
+/// Render the report as a standalone HTML page
+#[must_use]
+pub fn html(report: &Report) -> String {
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str("<title>Dependency report</title>\n");
+    let _ = writeln!(out, "<style>{STYLE}</style>");
+    out.push_str("</head>\n<body>\n<h1>Dependency report</h1>\n");
+
+    out.push_str("<h2>Dependency trees</h2>\n");
+    for tree in &report.trees {
+        out.push_str("<ul>");
+        tree_html(&mut out, tree);
+        out.push_str("</ul>\n");
+    }
+
+    out.push_str("<h2>Duplicates</h2>\n");
+    if report.duplicates.is_empty() {
+        out.push_str("<p>All packages are present in one version only</p>\n");
+    } else {
+        out.push_str("<table>\n<tr><th>Crate</th><th>Versions</th></tr>\n");
+        for dupe in &report.duplicates.crates {
+            let versions = dupe
+                .versions
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td></tr>",
+                escape(&dupe.name),
+                escape(&versions)
+            );
+        }
+        out.push_str("</table>\n");
+    }
+
+    out.push_str("<h2>Pending hack changes</h2>\n");
+    if report.changeset.is_empty() {
+        out.push_str("<p>Features are unified as is</p>\n");
+    } else {
+        for member in &report.changeset.members {
+            let _ = writeln!(
+                out,
+                "<h3>{} {}</h3>\n<p class=\"note\">{}</p>",
+                escape(&member.name),
+                member.version,
+                escape(member.manifest_path.as_str())
+            );
+            out.push_str("<table>\n<tr><th>Dependency</th><th>Source</th><th>Features</th></tr>\n");
+            for change in &member.changes {
+                let dev = match change.ty {
+                    Ty::Dev => " <span class=\"dev\">(dev)</span>",
+                    Ty::Norm => "",
+                };
+                let feats = change.feats.iter().cloned().collect::<Vec<_>>().join(", ");
+                let _ = writeln!(
+                    out,
+                    "<tr><td>{} {}{dev}</td><td>{}</td><td>{}</td></tr>",
+                    escape(&change.name),
+                    change.version,
+                    escape(&change.source.to_string()),
+                    escape(&feats)
+                );
+            }
+            out.push_str("</table>\n");
+        }
+    }
+
+    out.push_str("<h2>Features</h2>\n");
+    out.push_str("<p class=\"note\">Features each crate is compiled with when the whole workspace is built</p>\n");
+    out.push_str("<table>\n<tr><th>Crate</th><th>Features</th></tr>\n");
+    for krate in &report.features {
+        let _ = writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            escape(&krate.label),
+            escape(&krate.features.join(", "))
+        );
+    }
+    out.push_str("</table>\n</body>\n</html>\n");
+    out
+}
+
+fn tree_html(out: &mut String, node: &TreeNode) {
+    let mut label = escape(&node.label);
+    if node.dev_only {
+        label.push_str(" <span class=\"dev\">(dev)</span>");
+    }
+    if node.repeated {
+        label.push_str(" <span class=\"note\">(*)</span>");
+    }
+    if node.children.is_empty() {
+        let _ = write!(out, "<li>{label}</li>");
+        return;
+    }
+    let _ = write!(out, "<li><details><summary>{label}</summary><ul>");
+    for child in &node.children {
+        tree_html(out, child);
+    }
+    out.push_str("</ul></details></li>");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hack::Collect, test_util::process_fg_with};
+
+    fn leaf(label: &str, dev_only: bool, repeated: bool) -> TreeNode {
+        TreeNode {
+            label: label.to_string(),
+            dev_only,
+            repeated,
+            children: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn trees_mark_repeated_packages() -> anyhow::Result<()> {
+        process_fg_with(11, |fg| {
+            fg.shrink_to_target(Collect::DevTarget)?;
+
+            let trees = member_trees(fg);
+            let beta = TreeNode {
+                label: "beta".to_string(),
+                dev_only: false,
+                repeated: false,
+                children: vec![leaf("alpha", true, true), leaf("gamma", false, false)],
+            };
+            assert_eq!(trees[0].label, "alpha");
+            assert_eq!(trees[0].children, vec![beta]);
+
+            let report = Report {
+                trees,
+                duplicates: Duplicates::default(),
+                changeset: Changeset::default(),
+                features: crate_features(fg),
+            };
+            let html = html(&report);
+            assert!(html.contains("<li><details><summary>beta</summary><ul>"));
+            assert!(html.contains("<p>Features are unified as is</p>"));
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,308 @@
+use crate::{
+    feat_graph::{Feat, FeatGraph, Pid},
+    hack::{collect_features_from, Collect, DetachedDepTree},
+    opts::Invocation,
+};
+use petgraph::visit::{Dfs, EdgeRef};
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::{debug, info};
+
+/// Crates compiled by a single cargo invocation
+#[derive(Debug, Default)]
+pub struct Build<'a> {
+    /// features each crate is compiled with
+    pub feats: BTreeMap<Pid<'a>, BTreeSet<&'a str>>,
+    /// normal and build dependencies of each crate within this build
+    pub deps: BTreeMap<Pid<'a>, BTreeSet<Pid<'a>>>,
+}
+
+/// Why a crate needs to be compiled again when switching between two invocations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rebuild<'a> {
+    /// crate itself is compiled with a different set of features
+    Features,
+    /// crate depends on a crate that needs to be compiled again
+    Dependency(Pid<'a>),
+}
+
+fn selected_members<'a>(
+    fg: &FeatGraph<'a>,
+    invocation: &Invocation,
+) -> anyhow::Result<Vec<Pid<'a>>> {
+    if invocation.packages.is_empty() {
+        return Ok(fg.workspace_members.iter().copied().collect());
+    }
+    invocation
+        .packages
+        .iter()
+        .map(|name| {
+            fg.workspace_members
+                .iter()
+                .copied()
+                .find(|pid| &pid.package().name == name)
+                .ok_or_else(|| anyhow::anyhow!("{name} is not a workspace member"))
+        })
+        .collect()
+}
+
+/// Compute features every crate is compiled with for a given cargo invocation
+pub fn simulate<'a>(fg: &mut FeatGraph<'a>, invocation: &Invocation) -> anyhow::Result<Build<'a>> {
+    let members = selected_members(fg, invocation)?;
+    let mut starts = members
+        .iter()
+        .map(|&member| (member, vec![fg[member]]))
+        .collect::<Vec<_>>();
+
+    for feat in invocation
+        .features
+        .iter()
+        .flat_map(|f| f.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|f| !f.is_empty())
+    {
+        let mut found = false;
+        for (member, nodes) in &mut starts {
+            let package = member.package();
+            let name = match feat.split_once('/') {
+                Some((krate, name)) if krate == package.name => name,
+                Some(_) => continue,
+                None => feat,
+            };
+            if let Some(&ix) = package
+                .features
+                .get_key_value(name)
+                .and_then(|(name, _)| fg.fid_cache.get(&member.named(name)))
+            {
+                nodes.push(ix);
+                found = true;
+            }
+        }
+        if !found {
+            anyhow::bail!("None of the selected packages contain feature {feat}");
+        }
+    }
+
+    // all the members are built by the same cargo invocation so weak dependency features
+    // are checked against everything collected so far, dev dependencies are only followed
+    // from the member that declares them
+    let mut walks = starts
+        .into_iter()
+        .map(|(member, nodes)| {
+            info!("==== Simulating {member:?}");
+            let filter = if invocation.all_targets {
+                Collect::MemberDev(member)
+            } else {
+                Collect::NoDev
+            };
+            let mut dfs = Dfs::empty(&fg.features);
+            dfs.stack.extend(nodes);
+            (dfs, filter)
+        })
+        .collect::<Vec<_>>();
+    let mut deps_feats = DetachedDepTree::new();
+    loop {
+        for (dfs, filter) in &mut walks {
+            collect_features_from(dfs, fg, &mut deps_feats, *filter);
+        }
+        let reached = deps_feats
+            .values()
+            .flatten()
+            .copied()
+            .collect::<BTreeSet<_>>();
+        let mut found = false;
+        for (dfs, filter) in &mut walks {
+            let weak_feats = fg
+                .active_triggers(&reached, *filter)
+                .into_iter()
+                .filter_map(|(_, weak_feat)| fg.fid_cache.get(&weak_feat).copied())
+                .filter(|ix| !reached.contains(ix))
+                .collect::<Vec<_>>();
+            found |= !weak_feats.is_empty();
+            dfs.stack.extend(weak_feats);
+        }
+        if !found {
+            break;
+        }
+    }
+
+    let mut build = Build::default();
+    let mut visited = BTreeSet::new();
+    for (base, feats) in deps_feats {
+        let pid = match fg.features[base].pid() {
+            Some(pid) => pid,
+            None => continue,
+        };
+        let enabled = build.feats.entry(pid).or_default();
+        for feat in feats {
+            visited.insert(feat);
+            if let Some(Feat::Named(name)) = fg.features[feat].fid().map(|fid| fid.dep) {
+                enabled.insert(name);
+            }
+        }
+    }
+
+    // dev dependencies don't affect the library itself so they don't count
+    for &node in &visited {
+        let source = fg.features[node];
+        let pid = match source.pid() {
+            Some(pid) => pid,
+            None => continue,
+        };
+        for edge in fg.features.edges(node) {
+            if !edge
+                .weight()
+                .satisfies(source, Collect::NoDev, &fg.platforms, &fg.cfgs)
+            {
+                continue;
+            }
+            if let Some(dep) = fg.features[edge.target()].pid() {
+                if dep != pid && build.feats.contains_key(&dep) {
+                    build.deps.entry(pid).or_default().insert(dep);
+                }
+            }
+        }
+    }
+
+    debug!("Simulated build contains {} crates", build.feats.len());
+    Ok(build)
+}
+
+/// Crates present in both builds that need to be compiled again when switching between them
+///
+/// A crate is compiled again if its own feature set differs or any of its dependencies
+/// are compiled again.
+#[must_use]
+pub fn compare<'a>(old: &Build<'a>, new: &Build<'a>) -> BTreeMap<Pid<'a>, Rebuild<'a>> {
+    let mut rebuilds = BTreeMap::new();
+    for (pid, feats) in &new.feats {
+        if old.feats.get(pid).is_some_and(|old| old != feats) {
+            rebuilds.insert(*pid, Rebuild::Features);
+        }
+    }
+
+    loop {
+        let mut changed = false;
+        for (pid, deps) in &new.deps {
+            if rebuilds.contains_key(pid) || !old.feats.contains_key(pid) {
+                continue;
+            }
+            let old_deps = old.deps.get(pid);
+            if let Some(&dep) = deps.iter().find(|dep| {
+                rebuilds.contains_key(*dep) || old_deps.is_none_or(|d| !d.contains(*dep))
+            }) {
+                rebuilds.insert(*pid, Rebuild::Dependency(dep));
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    rebuilds
+}
+
+/// Distinct feature sets crates are compiled with and builds that produce them
+///
+/// `None` stands for the whole workspace, `Some(member)` for a member built alone
+pub type Variants<'a> = BTreeMap<Pid<'a>, BTreeMap<BTreeSet<&'a str>, Vec<Option<Pid<'a>>>>>;
+
+/// Find crates compiled with more than one set of features when building the whole
+/// workspace and each member individually
+pub fn variants<'a>(fg: &mut FeatGraph<'a>, all_targets: bool) -> anyhow::Result<Variants<'a>> {
+    let mut builds = vec![(None, Vec::new())];
+    for member in &fg.workspace_members {
+        builds.push((Some(*member), vec![member.package().name.clone()]));
+    }
+
+    let mut variants = Variants::new();
+    for (origin, packages) in builds {
+        let invocation = Invocation {
+            packages,
+            features: Vec::new(),
+            all_targets,
+        };
+        for (pid, feats) in simulate(fg, &invocation)?.feats {
+            variants
+                .entry(pid)
+                .or_default()
+                .entry(feats)
+                .or_default()
+                .push(origin);
+        }
+    }
+    variants.retain(|_, feats| feats.len() > 1);
+    Ok(variants)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::process_fg_with;
+
+    fn invocation(packages: &[&str]) -> Invocation {
+        Invocation {
+            packages: packages.iter().map(|p| p.to_string()).collect(),
+            features: Vec::new(),
+            all_targets: false,
+        }
+    }
+
+    #[test]
+    fn weak_dependency_causes_rebuild() -> anyhow::Result<()> {
+        process_fg_with(5, |fg| {
+            let alpha = simulate(fg, &invocation(&["alpha"]))?;
+            let beta = simulate(fg, &invocation(&["beta"]))?;
+            let rebuilds = compare(&alpha, &beta)
+                .into_iter()
+                .map(|(pid, reason)| (pid.package().name.as_str(), reason))
+                .collect::<Vec<_>>();
+            assert_eq!(rebuilds.len(), 2);
+            assert!(rebuilds.contains(&("gamma", Rebuild::Features)));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn weak_dependency_enabled_by_other_member() -> anyhow::Result<()> {
+        process_fg_with(13, |fg| {
+            let feats = |build: &Build, name: &str| {
+                build
+                    .feats
+                    .iter()
+                    .find(|(pid, _)| pid.package().name == name)
+                    .map(|(_, feats)| feats.iter().map(|f| f.to_string()).collect::<Vec<_>>())
+            };
+            // beta enables optional gamma, "gamma?/one" in alpha doesn't pull it in on its own
+            let beta = simulate(fg, &invocation(&["beta"]))?;
+            assert_eq!(feats(&beta, "gamma"), Some(vec!["one".to_string()]));
+            let alpha = simulate(fg, &invocation(&["alpha"]))?;
+            assert_eq!(feats(&alpha, "gamma"), None);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn weak_dependency_enabled_across_members() -> anyhow::Result<()> {
+        process_fg_with(14, |fg| {
+            // a enables "x?/y" on d, b enables optional x on d, together they enable x/y
+            let build = simulate(fg, &invocation(&["a", "b"]))?;
+            let x = build
+                .feats
+                .iter()
+                .find(|(pid, _)| pid.package().name == "x")
+                .map(|(_, feats)| feats.iter().copied().collect::<Vec<_>>());
+            assert_eq!(x, Some(vec!["y"]));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn hack_removes_variants() -> anyhow::Result<()> {
+        process_fg_with(5, |fg| {
+            let before = variants(fg, false)?;
+            assert_eq!(before.len(), 1);
+            crate::hack::get_changeset(fg, false)?;
+            assert!(variants(fg, false)?.is_empty());
+            Ok(())
+        })
+    }
+}
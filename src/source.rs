@@ -88,16 +88,16 @@ mod tests {
     }
 }
 
-impl<'a> TryFrom<&'a str> for PackageSource<'a> {
+impl TryFrom<&str> for PackageSource {
     type Error = anyhow::Error;
-    fn try_from(value: &'a str) -> Result<Self, Self::Error> {
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
         if let Some(registry) = value.strip_prefix("registry+") {
-            Ok(PackageSource::Registry(registry))
+            Ok(PackageSource::Registry(registry.to_string()))
         } else if let Some(repo) = value.strip_prefix("git+") {
             if let Some((url, _)) = repo.split_once('#') {
-                Ok(PackageSource::Git(url))
+                Ok(PackageSource::Git(url.to_string()))
             } else {
-                Ok(PackageSource::Git(repo))
+                Ok(PackageSource::Git(repo.to_string()))
             }
         } else {
             anyhow::bail!("Not sure what package source is {value}");
@@ -105,11 +105,11 @@ impl<'a> TryFrom<&'a str> for PackageSource<'a> {
     }
 }
 
-impl<'a> ChangePackage<'a> {
+impl ChangePackage {
     #[allow(clippy::similar_names)]
     pub fn make(
-        importer: Pid<'a>,
-        importee: Pid<'a>,
+        importer: Pid,
+        importee: Pid,
         ty: Ty,
         rename: bool,
         mut feats: BTreeSet<String>,
@@ -164,23 +164,23 @@ fn relative_import_dir(importer: Pid, importee: Pid) -> Option<Utf8PathBuf> {
 }
 
 #[derive(Debug)]
-pub struct ChangePackage<'a> {
+pub struct ChangePackage {
     pub name: String,
     pub ty: Ty,
     pub version: Version,
-    pub source: PackageSource<'a>,
+    pub source: PackageSource,
     pub feats: BTreeSet<String>,
     pub rename: bool,
 }
 
-impl PackageSource<'_> {
+impl PackageSource {
     pub fn insert_into(&self, ver: &Version, table: &mut toml_edit::InlineTable) {
         match self {
             PackageSource::Registry(_) => {
                 table.insert("version", toml_edit::Value::from(ver.to_string()));
             }
             PackageSource::Git(url) => {
-                table.insert("git", toml_edit::Value::from(*url));
+                table.insert("git", toml_edit::Value::from(url.as_str()));
             }
             PackageSource::File { path } => {
                 table.insert("path", toml_edit::Value::from(path.to_string()));
@@ -191,18 +191,20 @@ impl PackageSource<'_> {
 
 #[derive(Debug, Hash)]
 #[allow(clippy::module_name_repetitions)]
-pub enum PackageSource<'a> {
-    Registry(&'a str),
-    Git(&'a str),
+pub enum PackageSource {
+    Registry(String),
+    Git(String),
     File { path: Utf8PathBuf },
 }
 
-impl PackageSource<'_> {
-    pub const CRATES_IO: Self =
-        PackageSource::Registry("https://github.com/rust-lang/crates.io-index");
+impl PackageSource {
+    #[must_use]
+    pub fn crates_io() -> Self {
+        PackageSource::Registry(String::from("https://github.com/rust-lang/crates.io-index"))
+    }
 }
 
-impl std::fmt::Display for PackageSource<'_> {
+impl std::fmt::Display for PackageSource {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             PackageSource::Registry(_reg) => f.write_str("registry"),
@@ -0,0 +1,372 @@
+//! Self-contained SVG rendering with a simple layered layout
+//!
+//! Nodes are assigned to layers by the longest path from the sources, ordered within
+//! layers with a few rounds of barycenter heuristic to reduce crossings and edges are
+//! drawn as curves between the layers. This is nowhere near graphviz, but it doesn't need
+//! anything installed.
+
+use crate::{
+    explain::{ExplainResult, Node},
+    html::escape,
+    render::label_lines,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+const CHAR_WIDTH: f64 = 7.5;
+const LINE_HEIGHT: f64 = 16.0;
+const PADDING: f64 = 10.0;
+const NODE_GAP: f64 = 20.0;
+const LAYER_GAP: f64 = 60.0;
+const MARGIN: f64 = 20.0;
+const SWEEPS: usize = 8;
+
+struct Placed<'a> {
+    node: &'a Node,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+fn node_size(node: &Node) -> (f64, f64) {
+    let lines = label_lines(node);
+    let chars = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let width = chars as f64 * CHAR_WIDTH + 2.0 * PADDING;
+    let height = lines.len() as f64 * LINE_HEIGHT + PADDING;
+    // octagons and ellipses need some extra room for the corners
+    match node.feature {
+        None => (width + 2.0 * PADDING, height),
+        Some(_) => (width + 4.0 * PADDING, height + PADDING),
+    }
+}
+
+/// Assign each node to a layer so that edges point downwards, ignoring edges closing cycles
+fn layers(result: &ExplainResult) -> BTreeMap<usize, usize> {
+    let mut succs = BTreeMap::<usize, Vec<usize>>::new();
+    for edge in &result.edges {
+        succs.entry(edge.from).or_default().push(edge.to);
+    }
+
+    // find back edges with DFS, anything else forms a DAG
+    let mut back = BTreeSet::new();
+    let mut state = BTreeMap::<usize, bool>::new(); // false - in progress, true - done
+    let mut order = Vec::new();
+    for node in &result.nodes {
+        if state.contains_key(&node.id) {
+            continue;
+        }
+        let mut stack = vec![(node.id, 0)];
+        state.insert(node.id, false);
+        while let Some((id, ix)) = stack.pop() {
+            let next = succs.get(&id).and_then(|s| s.get(ix)).copied();
+            match next {
+                Some(next) => {
+                    stack.push((id, ix + 1));
+                    match state.get(&next) {
+                        Some(false) => {
+                            back.insert((id, next));
+                        }
+                        Some(true) => {}
+                        None => {
+                            state.insert(next, false);
+                            stack.push((next, 0));
+                        }
+                    }
+                }
+                None => {
+                    state.insert(id, true);
+                    order.push(id);
+                }
+            }
+        }
+    }
+
+    // reversed post order is a topological order of the DAG
+    let mut layer = result
+        .nodes
+        .iter()
+        .map(|node| (node.id, 0))
+        .collect::<BTreeMap<_, _>>();
+    for &id in order.iter().rev() {
+        let this = layer[&id];
+        for &next in succs.get(&id).into_iter().flatten() {
+            if !back.contains(&(id, next)) && layer[&next] <= this {
+                layer.insert(next, this + 1);
+            }
+        }
+    }
+    layer
+}
+
+/// Order nodes within each layer, nodes are moved towards the average position of neighbours
+fn order_layers(result: &ExplainResult, layer: &BTreeMap<usize, usize>) -> Vec<Vec<usize>> {
+    let depth = layer.values().max().map_or(0, |d| d + 1);
+    let mut rows = vec![Vec::new(); depth];
+    for node in &result.nodes {
+        rows[layer[&node.id]].push(node.id);
+    }
+
+    let mut neighbours = BTreeMap::<usize, Vec<usize>>::new();
+    for edge in &result.edges {
+        neighbours.entry(edge.from).or_default().push(edge.to);
+        neighbours.entry(edge.to).or_default().push(edge.from);
+    }
+
+    for sweep in 0..SWEEPS {
+        let position = rows
+            .iter()
+            .flat_map(|row| row.iter().enumerate().map(|(ix, &id)| (id, ix as f64)))
+            .collect::<BTreeMap<_, _>>();
+        let range = if sweep % 2 == 0 {
+            (1..depth).collect::<Vec<_>>()
+        } else {
+            (0..depth.saturating_sub(1)).rev().collect::<Vec<_>>()
+        };
+        for l in range {
+            // neighbours in the layer we came from
+            let from = if sweep % 2 == 0 { l - 1 } else { l + 1 };
+            let mut keyed = rows[l]
+                .iter()
+                .enumerate()
+                .map(|(ix, &id)| {
+                    let adjacent = neighbours
+                        .get(&id)
+                        .into_iter()
+                        .flatten()
+                        .filter(|n| layer[n] == from)
+                        .map(|n| position[n])
+                        .collect::<Vec<_>>();
+                    let key = if adjacent.is_empty() {
+                        ix as f64
+                    } else {
+                        adjacent.iter().sum::<f64>() / adjacent.len() as f64
+                    };
+                    (key, id)
+                })
+                .collect::<Vec<_>>();
+            keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+            rows[l] = keyed.into_iter().map(|(_, id)| id).collect();
+        }
+    }
+    rows
+}
+
+fn octagon(p: &Placed) -> String {
+    let (x, y, w, h) = (p.x, p.y, p.width, p.height);
+    let c = (h / 3.0).min(w / 3.0);
+    [
+        (x + c, y),
+        (x + w - c, y),
+        (x + w, y + c),
+        (x + w, y + h - c),
+        (x + w - c, y + h),
+        (x + c, y + h),
+        (x, y + h - c),
+        (x, y + c),
+    ]
+    .iter()
+    .map(|(x, y)| format!("{x:.1},{y:.1}"))
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// Render the result as a standalone SVG image
+///
+/// Uses the same conventions as the dot output: base crates are octagons, external crates are
+/// filled, duplicated crates are gold, crates linking to native libraries are blue, dev-only
+/// links are dashed, build-only links are thick, weak links are dotted and optional links are
+/// grey.
+#[must_use]
+pub fn svg(result: &ExplainResult) -> String {
+    let layer = layers(result);
+    let rows = order_layers(result, &layer);
+    let nodes = result
+        .nodes
+        .iter()
+        .map(|node| (node.id, node))
+        .collect::<BTreeMap<_, _>>();
+
+    let row_width = |row: &Vec<usize>| {
+        row.iter().map(|id| node_size(nodes[id]).0).sum::<f64>()
+            + NODE_GAP * row.len().saturating_sub(1) as f64
+    };
+    let width = rows.iter().map(row_width).fold(0.0, f64::max);
+
+    let mut placed = BTreeMap::new();
+    let mut y = MARGIN;
+    for row in &rows {
+        let height = row
+            .iter()
+            .map(|id| node_size(nodes[id]).1)
+            .fold(0.0, f64::max);
+        let mut x = MARGIN + (width - row_width(row)) / 2.0;
+        for id in row {
+            let node = nodes[id];
+            let (w, h) = node_size(node);
+            placed.insert(
+                *id,
+                Placed {
+                    node,
+                    x,
+                    y: y + (height - h) / 2.0,
+                    width: w,
+                    height: h,
+                },
+            );
+            x += w + NODE_GAP;
+        }
+        y += height + LAYER_GAP;
+    }
+    let total_width = width + 2.0 * MARGIN;
+    let total_height = y - LAYER_GAP + MARGIN;
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{total_width:.0}" height="{total_height:.0}" viewBox="0 0 {total_width:.0} {total_height:.0}" font-family="monospace" font-size="12">"#
+    );
+    out.push_str(
+        r#"<defs><marker id="arrow" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="8" markerHeight="8" orient="auto-start-reverse"><path d="M 0 0 L 10 5 L 0 10 z"/></marker></defs>
+"#,
+    );
+
+    for edge in &result.edges {
+        let (from, to) = match (placed.get(&edge.from), placed.get(&edge.to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => continue,
+        };
+        let x1 = from.x + from.width / 2.0;
+        let x2 = to.x + to.width / 2.0;
+        let (y1, y2) = if to.y > from.y {
+            (from.y + from.height, to.y)
+        } else {
+            (from.y, to.y + to.height)
+        };
+        let bend = (y2 - y1) / 2.0;
+        let color = if edge.optional { "grey" } else { "black" };
+        let dash = if edge.weak {
+            r#" stroke-dasharray="2,3""#
+        } else if edge.dev_only {
+            r#" stroke-dasharray="6,4""#
+        } else if edge.build_only {
+            r#" stroke-width="2.5""#
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            out,
+            r#"<path d="M {x1:.1} {y1:.1} C {x1:.1} {:.1}, {x2:.1} {:.1}, {x2:.1} {y2:.1}" fill="none" stroke="{color}"{dash} marker-end="url(#arrow)"/>"#,
+            y1 + bend,
+            y2 - bend,
+        );
+        if let Some(label) = &edge.label {
+            let _ = writeln!(
+                out,
+                r#"<text x="{:.1}" y="{:.1}" fill="grey">{}</text>"#,
+                (x1 + x2) / 2.0 + 4.0,
+                (y1 + y2) / 2.0,
+                escape(label)
+            );
+        }
+    }
+
+    for p in placed.values() {
+        let fill = if p.node.target {
+            "pink"
+        } else if p.node.cycle {
+            "orange"
+        } else if p.node.duplicate {
+            "gold"
+        } else if p.node.links.is_some() {
+            "lightblue"
+        } else if p.node.workspace {
+            "white"
+        } else {
+            "lightgrey"
+        };
+        let _ = match p.node.feature {
+            None => writeln!(
+                out,
+                r#"<polygon points="{}" fill="{fill}" stroke="black"/>"#,
+                octagon(p)
+            ),
+            Some(_) => writeln!(
+                out,
+                r#"<ellipse cx="{:.1}" cy="{:.1}" rx="{:.1}" ry="{:.1}" fill="{fill}" stroke="black"/>"#,
+                p.x + p.width / 2.0,
+                p.y + p.height / 2.0,
+                p.width / 2.0,
+                p.height / 2.0
+            ),
+        };
+        let lines = label_lines(p.node);
+        let top = p.y + (p.height - lines.len() as f64 * LINE_HEIGHT) / 2.0 + LINE_HEIGHT * 0.75;
+        for (ix, line) in lines.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                r#"<text x="{:.1}" y="{:.1}" text-anchor="middle">{}</text>"#,
+                p.x + p.width / 2.0,
+                top + ix as f64 * LINE_HEIGHT,
+                escape(line)
+            );
+        }
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::explain::Edge;
+
+    fn node(id: usize, label: &str) -> Node {
+        Node {
+            id,
+            package: None,
+            feature: None,
+            label: label.to_string(),
+            workspace: true,
+            target: false,
+            cycle: false,
+            duplicate: false,
+            proc_macro: false,
+            build_script: false,
+            links: None,
+            reach: None,
+        }
+    }
+
+    fn edge(from: usize, to: usize) -> Edge {
+        Edge {
+            from,
+            to,
+            optional: false,
+            dev_only: false,
+            build_only: false,
+            weak: false,
+            label: None,
+        }
+    }
+
+    #[test]
+    fn layers_follow_edges_and_survive_cycles() {
+        let result = ExplainResult {
+            nodes: vec![node(0, "alpha"), node(1, "beta"), node(2, "gamma")],
+            edges: vec![edge(0, 1), edge(1, 2), edge(0, 2), edge(2, 1)],
+            dot: String::new(),
+        };
+        let layer = layers(&result);
+        assert_eq!(layer[&0], 0);
+        assert_eq!(layer[&1], 1);
+        assert_eq!(layer[&2], 2);
+
+        let svg = svg(&result);
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<polygon").count(), 3);
+        assert_eq!(svg.matches("marker-end").count(), 4);
+    }
+}
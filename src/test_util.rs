@@ -0,0 +1,24 @@
+use crate::feat_graph::FeatGraph;
+
+/// Metadata for one of the workspaces in `test_workspaces`
+pub(crate) fn get_demo_meta(ix: usize) -> anyhow::Result<cargo_metadata::Metadata> {
+    let path = format!(
+        "{}/test_workspaces/{ix}/metadata.json",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let data = std::fs::read_to_string(path)?;
+    Ok(cargo_metadata::MetadataCommand::parse(data)?)
+}
+
+/// Run `op` on the feature graph of one of the workspaces in `test_workspaces` for the
+/// current target
+pub(crate) fn process_fg_with<F, T>(ix: usize, op: F) -> anyhow::Result<T>
+where
+    F: FnOnce(&mut FeatGraph) -> anyhow::Result<T>,
+{
+    let meta = get_demo_meta(ix)?;
+    let platform = target_spec::Platform::current()?;
+    let triplets = vec![platform.triple_str()];
+    let mut fg = FeatGraph::init(&meta, triplets, Vec::new())?;
+    op(&mut fg)
+}
@@ -411,7 +411,7 @@ package = 1.0
             name: "package".to_string(),
             ty: Ty::Norm,
             version: Version::new(1, 0, 0),
-            source: PackageSource::CRATES_IO,
+            source: PackageSource::crates_io(),
             feats,
             rename: false,
         }];
@@ -446,7 +446,7 @@ package = 1.0
                 name: "package".to_string(),
                 ty: Ty::Norm,
                 version: Version::new(1, 0, 0),
-                source: PackageSource::CRATES_IO,
+                source: PackageSource::crates_io(),
                 feats,
                 rename: false,
             }];
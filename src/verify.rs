@@ -0,0 +1,216 @@
+use crate::{
+    feat_graph::{Feat, FeatGraph, Pid},
+    hack::{collect_features_with_triggers, Collect},
+};
+use cargo_metadata::{camino::Utf8PathBuf, Metadata, Version};
+use petgraph::visit::Dfs;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+use tracing::{debug, info};
+
+/// Enabled features per package, package is identified by name and version
+type EnabledFeats = BTreeMap<(String, Version), BTreeSet<String>>;
+
+/// Parse output of `cargo tree -e features --prefix none --format "{p};{f}"`
+///
+/// Output consists of two kinds of lines:
+/// - `serde v1.0.136 (source);default,std` for packages along with all the enabled features
+/// - `serde feature "std"` for feature edges, those are already covered by the package lines
+fn parse_cargo_tree(output: &str) -> anyhow::Result<EnabledFeats> {
+    let mut res = EnabledFeats::new();
+    for line in output.lines() {
+        let (package, feats) = match line.rsplit_once(';') {
+            Some(pair) => pair,
+            None => continue,
+        };
+        let mut words = package.split_whitespace();
+        let (name, version) = match (words.next(), words.next().and_then(|v| v.strip_prefix('v'))) {
+            (Some(name), Some(version)) => (name, Version::parse(version)?),
+            _ => anyhow::bail!("Unexpected cargo tree line: {line:?}"),
+        };
+        let enabled = res.entry((name.to_string(), version)).or_default();
+        // features can be followed by markers such as (*), list itself can be empty
+        let mut feats = feats.trim();
+        while let Some(rest) = ["(*)", "(proc-macro)"]
+            .iter()
+            .find_map(|marker| feats.strip_suffix(marker))
+        {
+            feats = rest.trim_end();
+        }
+        if !feats.is_empty() {
+            enabled.extend(feats.split(',').map(String::from));
+        }
+    }
+    Ok(res)
+}
+
+fn cargo_tree_feats(manifest_path: &Path, member: Pid) -> anyhow::Result<EnabledFeats> {
+    let package = member.package();
+    let spec = format!("{}@{}", package.name, package.version);
+    let output = std::process::Command::new("cargo")
+        .args(["tree", "-e", "features", "--prefix", "none", "--offline"])
+        .args(["--format", "{p};{f}"])
+        .arg("--manifest-path")
+        .arg(manifest_path)
+        .args(["-p", &spec])
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "cargo tree failed for {spec}:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    parse_cargo_tree(&String::from_utf8(output.stdout)?)
+}
+
+fn hackerman_feats(fg: &FeatGraph, member: Pid) -> EnabledFeats {
+    let mut deps_feats = BTreeMap::new();
+    let mut dfs = Dfs::new(&fg.features, fg[member]);
+    collect_features_with_triggers(&mut dfs, fg, &mut deps_feats, Collect::MemberDev(member));
+
+    let mut res = EnabledFeats::new();
+    for (base, feats) in deps_feats {
+        let pid = match fg.features[base].pid() {
+            Some(pid) => pid,
+            None => continue,
+        };
+        let package = pid.package();
+        let enabled = res
+            .entry((package.name.clone(), package.version.clone()))
+            .or_default();
+        for feat in feats {
+            if let Some(Feat::Named(name)) = fg.features[feat].fid().map(|fid| fid.dep) {
+                enabled.insert(name.to_string());
+            }
+        }
+    }
+    res
+}
+
+/// Package cargo and hackerman enable different features for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub name: String,
+    pub version: Version,
+    /// declared features enabled by cargo, `None` if cargo doesn't build the package
+    pub cargo: Option<BTreeSet<String>>,
+    /// declared features expected by hackerman, `None` if hackerman doesn't expect it
+    pub hackerman: Option<BTreeSet<String>>,
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Mismatch { name, version, .. } = self;
+        match (&self.cargo, &self.hackerman) {
+            (Some(cargo), Some(hackerman)) => {
+                write!(
+                    f,
+                    "{name} {version}: cargo {cargo:?}, hackerman {hackerman:?}"
+                )
+            }
+            (Some(_), None) => write!(f, "{name} {version}: missing from hackerman"),
+            (None, _) => write!(f, "{name} {version}: missing from cargo"),
+        }
+    }
+}
+
+/// Disagreements found for a single workspace member
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberMismatches {
+    pub manifest_path: Utf8PathBuf,
+    pub mismatches: Vec<Mismatch>,
+}
+
+/// Cross-check features hackerman predicts for each workspace member against `cargo tree`
+///
+/// Only features declared by packages are compared: cargo reports `default` for packages
+/// without one and hackerman keeps a node for every optional dependency. Members without
+/// disagreements are left out.
+pub fn verify_model(
+    fg: &mut FeatGraph,
+    meta: &Metadata,
+    manifest_path: &Path,
+) -> anyhow::Result<Vec<MemberMismatches>> {
+    let mut res = Vec::new();
+    let members = fg.workspace_members.clone();
+    for member in members {
+        info!("==== Verifying {member:?}");
+        let expected = cargo_tree_feats(manifest_path, member)?;
+        let predicted = hackerman_feats(fg, member);
+
+        let keys = expected
+            .keys()
+            .chain(predicted.keys())
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        let mut mismatches = Vec::new();
+        for key @ (name, version) in &keys {
+            let declared = |feats: Option<&BTreeSet<String>>| {
+                feats.map(|feats| {
+                    feats
+                        .iter()
+                        .filter(|f| is_declared(meta, name, version, f))
+                        .cloned()
+                        .collect::<BTreeSet<_>>()
+                })
+            };
+            let cargo = declared(expected.get(key));
+            let hackerman = declared(predicted.get(key));
+            if cargo == hackerman {
+                debug!("{name} {version}: ok");
+            } else {
+                mismatches.push(Mismatch {
+                    name: name.clone(),
+                    version: version.clone(),
+                    cargo,
+                    hackerman,
+                });
+            }
+        }
+
+        if !mismatches.is_empty() {
+            res.push(MemberMismatches {
+                manifest_path: member.package().manifest_path.clone(),
+                mismatches,
+            });
+        }
+    }
+    Ok(res)
+}
+
+fn is_declared(meta: &Metadata, name: &str, version: &Version, feat: &str) -> bool {
+    meta.packages
+        .iter()
+        .find(|p| p.name == name && &p.version == version)
+        .is_none_or(|p| p.features.contains_key(feat))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cargo_tree_output() -> anyhow::Result<()> {
+        let output = r#"alpha v0.1.0 (/ws/alpha);default,one
+beta feature "default"
+beta v0.1.0 (/ws/beta);
+gamma feature "default"
+gamma v0.1.0 (/ws/gamma);one
+gamma feature "default" (*)
+delta v0.1.0 (/ws/delta); (*)
+epsilon v0.1.0 (/ws/epsilon);derive (proc-macro) (*)
+"#;
+        let feats = parse_cargo_tree(output)?;
+        let v010 = Version::new(0, 1, 0);
+        assert_eq!(feats.len(), 5);
+        assert_eq!(feats[&("alpha".to_string(), v010.clone())].len(), 2);
+        assert!(feats[&("beta".to_string(), v010.clone())].is_empty());
+        assert!(feats[&("gamma".to_string(), v010.clone())].contains("one"));
+        assert!(feats[&("delta".to_string(), v010.clone())].is_empty());
+        let epsilon = &feats[&("epsilon".to_string(), v010)];
+        assert_eq!(epsilon.iter().collect::<Vec<_>>(), ["derive"]);
+        Ok(())
+    }
+}
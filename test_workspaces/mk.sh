@@ -7,3 +7,7 @@
 #cargo metadata --manifest-path 4/alpha/Cargo.toml --format-version 1 > 4/metadata.json
 cargo metadata --manifest-path 5/Cargo.toml --format-version 1 > 5/metadata.json
 
+cargo metadata --manifest-path 11/Cargo.toml --format-version 1 > 11/metadata.json
+cargo metadata --manifest-path 12/Cargo.toml --format-version 1 > 12/metadata.json
+cargo metadata --manifest-path 13/Cargo.toml --format-version 1 > 13/metadata.json
+cargo metadata --manifest-path 14/Cargo.toml --format-version 1 > 14/metadata.json